@@ -0,0 +1,80 @@
+use std::{fmt, io, path::PathBuf};
+
+
+/// An error found in the configuration json.
+/// Every error has the json path of the offending field, e.g. `Servers[2].port`,
+/// empty if the error concerns the whole file.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// a file (the configuration or an error page) can't be read
+    Io {
+        path: String,
+        file: PathBuf,
+        error: io::Error
+    },
+    /// the file isn't a correct json
+    Json(serde_json::Error),
+    /// a required key is missing
+    MissingKey {
+        path: String
+    },
+    /// a key has a value of the wrong type or out of range
+    InvalidValue {
+        path: String,
+        reason: &'static str
+    },
+    /// the hostname of a server can't be resolved
+    Resolve {
+        path: String,
+        host: String,
+        error: io::Error
+    }
+}
+
+impl ConfigError {
+    /// Create an error for a value of the wrong type or out of range
+    /// # Arguments
+    ///
+    /// * `path` - json path of the value
+    /// * `reason` - what the value should be
+    pub fn invalid(path: impl Into<String>, reason: &'static str) -> Self {
+        ConfigError::InvalidValue { path: path.into(), reason }
+    }
+
+    /// Return the json path of the offending field, empty for the whole file
+    pub fn path(&self) -> &str {
+        match self {
+            ConfigError::Io { path, .. } => path,
+            ConfigError::Json(_) => "",
+            ConfigError::MissingKey { path } => path,
+            ConfigError::InvalidValue { path, .. } => path,
+            ConfigError::Resolve { path, .. } => path
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path().is_empty() {
+            write!(f, "{}: ", self.path())?;
+        }
+        match self {
+            ConfigError::Io { file, error, .. } => write!(f, "Can't read {}: {}", file.display(), error),
+            ConfigError::Json(error) => write!(f, "The json format isn't correct: {}", error),
+            ConfigError::MissingKey { .. } => f.write_str("The key is missing"),
+            ConfigError::InvalidValue { reason, .. } => f.write_str(reason),
+            ConfigError::Resolve { host, error, .. } => write!(f, "Can't resolve {}: {}", host, error)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { error, .. } => Some(error),
+            ConfigError::Json(error) => Some(error),
+            ConfigError::Resolve { error, .. } => Some(error),
+            _ => None
+        }
+    }
+}
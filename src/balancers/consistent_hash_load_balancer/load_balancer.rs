@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use crate::{
+    server::socket_address::*,
+    balancers::{
+        hash::{hash_bytes, hash_client},
+        LoadBalancer,
+        RequestContext,
+        MAX_SERVERS,
+        TOO_MANY_SERVERS,
+        ZERO_OR_NEGATIVE_SERVERS
+    }
+};
+
+// Number of points on the ring for each unit of weight
+pub const VIRTUAL_NODES_PER_WEIGHT: usize = 160;
+// Max sum of the weights, about a million points on the ring
+pub const MAX_TOTAL_WEIGHT: usize = 6400;
+
+// Error messages
+pub static TOTAL_WEIGHT_TOO_HIGH: &str = "The sum of the weights is over 6400 for the consistent hash";
+
+/// The main struct for storing the Socket Address of each server
+/// and perform Consistent Hashing (ketama ring) on the client address.
+/// Every server owns "weight * VIRTUAL_NODES_PER_WEIGHT" points of the
+/// ring (the sum of the weights is at most MAX_TOTAL_WEIGHT), a client
+/// is sent to the server owning the first point after the hash of its
+/// IP address. The same client always lands on the same server, and
+/// adding or removing a server only remaps the clients of the ring
+/// segments it owns.
+#[derive(Debug)]
+pub struct ConsistentHashLB {
+    /// stores the Socket Address and the weight of each server
+    addresses: Vec<(SocketAddress, usize)>,
+    /// sum of the weights, at most MAX_TOTAL_WEIGHT
+    total_weight: usize,
+    /// the ring: hash of the point -> index in addresses,
+    /// the points of a server are added when it's inserted
+    ring: BTreeMap<u64, usize>
+}
+
+impl ConsistentHashLB {
+
+    /// Return the len of the inner vector
+    #[allow(dead_code)]
+    pub fn n_of_servers(&self) -> usize {
+        self.addresses.len()
+    }
+
+    /// Return the number of points on the ring
+    #[allow(dead_code)]
+    pub fn n_of_points(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Add the points of the server at `idx` to the ring
+    fn populate(&mut self, idx: usize) {
+        let (socket_address, weight) = &self.addresses[idx];
+        // the points depend only on the server, not on the insertion order
+        let name = socket_address.get();
+        for point in 0..(weight * VIRTUAL_NODES_PER_WEIGHT) {
+            let key = format!("{}-{}", name, point);
+            self.ring.entry(hash_bytes(key.as_bytes(), 0)).or_insert(idx);
+        }
+    }
+
+}
+
+impl LoadBalancer for ConsistentHashLB {
+
+    fn new(servers_number: usize) -> Result<Box<Self>, &'static str> {
+        if servers_number > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        if servers_number == 0 {
+            return Err(ZERO_OR_NEGATIVE_SERVERS);
+        }
+        Ok(Box::new(ConsistentHashLB {
+            addresses: Vec::with_capacity(servers_number),
+            total_weight: 0,
+            ring: BTreeMap::new()
+        }))
+    }
+
+    fn insert_socket_address(&mut self, socket_address: SocketAddress, weight: usize) -> Result<(), &'static str> {
+        if self.addresses.len() + 1 > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        self.total_weight = self.total_weight.checked_add(weight)
+            .filter(|total_weight| *total_weight <= MAX_TOTAL_WEIGHT)
+            .ok_or(TOTAL_WEIGHT_TOO_HIGH)?;
+        self.addresses.push((socket_address, weight));
+        self.populate(self.addresses.len() - 1);
+        Ok(())
+    }
+
+    fn next_server(&self, context: &RequestContext) -> Option<&SocketAddress> {
+        let hash = hash_client(context.client_address(), 0);
+
+        // all the weights are 0
+        if self.ring.is_empty() {
+            let len = self.addresses.len();
+            let idx = (hash % len as u64) as usize;
+            return (idx..len).chain(0..idx)
+                .map(|i| &self.addresses[i].0)
+                .find(|socket_address| context.is_available(socket_address));
+        }
+
+        // avoids scanning the whole ring when no server is available
+        if !self.addresses.iter().any(|(socket_address, weight)| {
+            *weight > 0 && context.is_available(socket_address)
+        }) {
+            return None;
+        }
+
+        // if the server isn't available, the owner of the next point
+        self.ring.range(hash..).chain(self.ring.range(..hash))
+            .map(|(_, idx)| &self.addresses[*idx].0)
+            .find(|socket_address| context.is_available(socket_address))
+    }
+}
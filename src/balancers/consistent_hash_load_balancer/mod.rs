@@ -0,0 +1 @@
+pub mod load_balancer;
\ No newline at end of file
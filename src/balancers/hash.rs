@@ -0,0 +1,41 @@
+use std::net::{IpAddr, SocketAddr};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+
+/// Stable 64 bit hash of some bytes: FNV-1a followed by the
+/// splitmix64 finalizer for a better distribution of the high bits.
+/// The result is the same on every run and every platform, unlike
+/// the std hasher, so the hashing balancers keep their mapping
+/// across restarts.
+/// # Arguments
+///
+/// * `bytes` - the bytes to hash
+/// * `seed` - different seeds give independent hash functions
+pub fn hash_bytes(bytes: &[u8], seed: u64) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS ^ seed.wrapping_mul(FNV_PRIME);
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    mix(hash)
+}
+
+/// The splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58476d1ce4e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Hash of the client of a connection.
+/// Only the IP address is used, the port changes at every connection.
+pub fn hash_client(client_address: &SocketAddr, seed: u64) -> u64 {
+    match client_address.ip() {
+        IpAddr::V4(ip) => hash_bytes(&ip.octets(), seed),
+        IpAddr::V6(ip) => hash_bytes(&ip.octets(), seed)
+    }
+}
@@ -0,0 +1,142 @@
+use std::{any::Any, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+use crate::{
+    server::socket_address::*,
+    balancers::{
+        LoadBalancer,
+        RequestContext,
+        MAX_SERVERS,
+        TOO_MANY_SERVERS,
+        ZERO_OR_NEGATIVE_SERVERS
+    }
+};
+
+/// A server with its weight and the number of in-flight connections
+#[derive(Debug)]
+struct Backend {
+    socket_address: SocketAddress,
+    weight: usize,
+    /// shared with the balancers of the next pools
+    active: Arc<AtomicUsize>
+}
+
+/// The main struct for storing the Socket Address of each server
+/// and perform Weighted Least Connections Algorithm.
+/// The chosen server is the one with the lowest ratio between
+/// in-flight connections and weight; ties are broken in round robin.
+#[derive(Debug)]
+pub struct LeastConnectionsLB {
+    /// stores the Socket Address of each server
+    backends: Vec<Backend>,
+    /// first index checked by the next search, used for breaking the ties
+    start: AtomicUsize,
+    /// true if every weight is 0, then every server has weight 1
+    all_zero: bool
+}
+
+impl LeastConnectionsLB {
+
+    /// Return the len of the inner vector
+    #[allow(dead_code)]
+    pub fn n_of_servers(&self) -> usize {
+        self.backends.len()
+    }
+
+    /// Return the weight used for a server, 1 if every weight is 0
+    fn weight(&self, backend: &Backend) -> usize {
+        if self.all_zero { 1 } else { backend.weight }
+    }
+
+    /// Return the number of in-flight connections of a server
+    #[allow(dead_code)]
+    pub fn active_connections(&self, socket_address: &SocketAddress) -> usize {
+        self.backends.iter()
+            .filter(|backend| backend.socket_address == *socket_address)
+            .map(|backend| backend.active.load(Ordering::Relaxed))
+            .sum()
+    }
+
+}
+
+impl LoadBalancer for LeastConnectionsLB {
+
+    fn new(servers_number: usize) -> Result<Box<Self>, &'static str> {
+        if servers_number > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        if servers_number == 0 {
+            return Err(ZERO_OR_NEGATIVE_SERVERS);
+        }
+        Ok(Box::new(LeastConnectionsLB {
+            backends: Vec::with_capacity(servers_number),
+            start: AtomicUsize::new(0),
+            all_zero: true
+        }))
+    }
+
+    fn insert_socket_address(&mut self, socket_address: SocketAddress, weight: usize) -> Result<(), &'static str> {
+        if self.backends.len() + 1 > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        self.backends.push(Backend {
+            socket_address,
+            weight,
+            active: Arc::new(AtomicUsize::new(0))
+        });
+        self.all_zero &= weight == 0;
+        Ok(())
+    }
+
+    fn next_server(&self, context: &RequestContext) -> Option<&SocketAddress> {
+        let len = self.backends.len();
+        let start = self.start.fetch_add(1, Ordering::Relaxed) % len;
+
+        // (index, active) of the best server found so far,
+        // servers with weight 0 or not available are never chosen
+        let mut best: Option<(usize, usize)> = None;
+        for i in (start..len).chain(0..start) {
+            let backend = &self.backends[i];
+            let weight = self.weight(backend);
+            if weight == 0 || !context.is_available(&backend.socket_address) {
+                continue;
+            }
+            let active = backend.active.load(Ordering::Relaxed);
+            // active / weight < best_active / best_weight
+            let is_better = match best {
+                None => true,
+                Some((b, best_active)) =>
+                    active * self.weight(&self.backends[b]) < best_active * weight
+            };
+            if is_better {
+                best = Some((i, active));
+            }
+        }
+
+        let backend = &self.backends[best?.0];
+        backend.active.fetch_add(1, Ordering::Relaxed);
+        Some(&backend.socket_address)
+    }
+
+    fn release(&self, socket_address: &SocketAddress) {
+        let backend = self.backends.iter().find(|backend| {
+            backend.socket_address == *socket_address
+                && backend.active.load(Ordering::Relaxed) > 0
+        });
+        if let Some(backend) = backend {
+            // never goes below 0, even with concurrent releases
+            let _ = backend.active.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                n.checked_sub(1)
+            });
+        }
+    }
+
+    fn inherit(&mut self, previous: &dyn LoadBalancer) {
+        if let Some(previous) = (previous as &dyn Any).downcast_ref::<Self>() {
+            for backend in self.backends.iter_mut() {
+                let old = previous.backends.iter().find(|old| old.socket_address == backend.socket_address);
+                if let Some(old) = old {
+                    backend.active = Arc::clone(&old.active);
+                }
+            }
+        }
+    }
+}
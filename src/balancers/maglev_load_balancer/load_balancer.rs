@@ -0,0 +1,208 @@
+use std::sync::OnceLock;
+use crate::{
+    server::socket_address::*,
+    balancers::{
+        hash::{hash_bytes, hash_client},
+        LoadBalancer,
+        RequestContext,
+        MAX_SERVERS,
+        TOO_MANY_SERVERS,
+        ZERO_OR_NEGATIVE_SERVERS
+    }
+};
+
+// Default size of the lookup table, must be a prime number
+pub const DEFAULT_TABLE_SIZE: usize = 65537;
+// Json key of the option for the table size
+static TABLE_SIZE_KEY: &str = "table_size";
+
+// Error messages
+pub static INVALID_TABLE_SIZE: &str = "The Maglev table size must be a prime number greater than the number of servers";
+
+/// The main struct for storing the Socket Address of each server
+/// and perform Maglev Hashing on the client address.
+/// Every server fills the entries of a prime-sized lookup table
+/// following its own permutation, a number of entries per turn equal
+/// to its weight. A client is sent to the server owning the entry at
+/// the hash of its IP address, so the lookup is O(1) and removing a
+/// server remaps few clients other than its own.
+#[derive(Debug)]
+pub struct MaglevLB {
+    /// stores the Socket Address and the weight of each server
+    addresses: Vec<(SocketAddress, usize)>,
+    /// size of the lookup table
+    table_size: usize,
+    /// the lookup table with the index in addresses of each entry,
+    /// built at the first request after the last change
+    table: OnceLock<Vec<u16>>
+}
+
+impl MaglevLB {
+
+    /// Return the len of the inner vector
+    #[allow(dead_code)]
+    pub fn n_of_servers(&self) -> usize {
+        self.addresses.len()
+    }
+
+    /// Set the size of the lookup table.
+    /// # Arguments
+    ///
+    /// * `table_size` - a prime number greater than the number of servers
+    ///
+    /// # Return
+    ///
+    /// * A result with empty Ok or an error string
+    pub fn set_table_size(&mut self, table_size: usize) -> Result<(), &'static str> {
+        if !is_prime(table_size) || table_size <= self.addresses.capacity() {
+            return Err(INVALID_TABLE_SIZE);
+        }
+        self.table_size = table_size;
+        self.table = OnceLock::new();
+        Ok(())
+    }
+
+    /// Return the number of entries of the lookup table owned by each server
+    #[allow(dead_code)]
+    pub fn entries_per_server(&self) -> Vec<usize> {
+        let mut entries = vec![0; self.addresses.len()];
+        for idx in self.table() {
+            entries[*idx as usize] += 1;
+        }
+        entries
+    }
+
+    /// Return the lookup table, building it if needed
+    fn table(&self) -> &Vec<u16> {
+        self.table.get_or_init(|| self.populate())
+    }
+
+    /// Build the lookup table as described in the Maglev paper,
+    /// with weighted turns. Servers with weight 0 own no entry.
+    fn populate(&self) -> Vec<u16> {
+        let size = self.table_size as u64;
+        let mut table = vec![u16::MAX; self.table_size];
+
+        // smaller weights with the same proportions give a finer interleaving
+        let divisor = self.addresses.iter().fold(0, |gcd, (_, weight)| greatest_common_divisor(gcd, *weight));
+        if divisor == 0 {
+            return Vec::new();
+        }
+
+        // (offset, skip, next) of the permutation of each server
+        let mut permutations: Vec<(u64, u64, u64)> = self.addresses.iter()
+            .map(|(socket_address, _)| {
+                let name = socket_address.get();
+                let offset = hash_bytes(name.as_bytes(), 1) % size;
+                let skip = hash_bytes(name.as_bytes(), 2) % (size - 1) + 1;
+                (offset, skip, 0)
+            })
+            .collect();
+
+        let mut filled = 0;
+        loop {
+            for (idx, (_, weight)) in self.addresses.iter().enumerate() {
+                let (offset, skip, next) = &mut permutations[idx];
+                for _ in 0..(weight / divisor) {
+                    // first free entry in the permutation of this server
+                    let mut entry = ((*offset + *next * *skip) % size) as usize;
+                    while table[entry] != u16::MAX {
+                        *next += 1;
+                        entry = ((*offset + *next * *skip) % size) as usize;
+                    }
+                    table[entry] = idx as u16;
+                    *next += 1;
+                    filled += 1;
+                    if filled == self.table_size {
+                        return table;
+                    }
+                }
+            }
+        }
+    }
+
+}
+
+impl LoadBalancer for MaglevLB {
+
+    fn new(servers_number: usize) -> Result<Box<Self>, &'static str> {
+        if servers_number > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        if servers_number == 0 {
+            return Err(ZERO_OR_NEGATIVE_SERVERS);
+        }
+        Ok(Box::new(MaglevLB {
+            addresses: Vec::with_capacity(servers_number),
+            table_size: DEFAULT_TABLE_SIZE,
+            table: OnceLock::new()
+        }))
+    }
+
+    fn set_options(&mut self, options: &serde_json::Value) -> Result<(), &'static str> {
+        match options.get(TABLE_SIZE_KEY) {
+            None => Ok(()),
+            Some(table_size) => match table_size.as_u64() {
+                Some(table_size) => self.set_table_size(table_size as usize),
+                None => Err(INVALID_TABLE_SIZE)
+            }
+        }
+    }
+
+    fn insert_socket_address(&mut self, socket_address: SocketAddress, weight: usize) -> Result<(), &'static str> {
+        if self.addresses.len() + 1 > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        if self.addresses.len() + 1 >= self.table_size {
+            return Err(INVALID_TABLE_SIZE);
+        }
+        self.addresses.push((socket_address, weight));
+        self.table = OnceLock::new();
+        Ok(())
+    }
+
+    fn next_server(&self, context: &RequestContext) -> Option<&SocketAddress> {
+        let hash = hash_client(context.client_address(), 0);
+        let table = self.table();
+
+        // all the weights are 0
+        if table.is_empty() {
+            let len = self.addresses.len();
+            let idx = (hash % len as u64) as usize;
+            return (idx..len).chain(0..idx)
+                .map(|i| &self.addresses[i].0)
+                .find(|socket_address| context.is_available(socket_address));
+        }
+
+        // avoids scanning the whole table when no server is available
+        if !self.addresses.iter().any(|(socket_address, weight)| {
+            *weight > 0 && context.is_available(socket_address)
+        }) {
+            return None;
+        }
+
+        // if the server isn't available, the owner of the next entry
+        let entry = (hash % table.len() as u64) as usize;
+        (entry..table.len()).chain(0..entry)
+            .map(|i| &self.addresses[table[i] as usize].0)
+            .find(|socket_address| context.is_available(socket_address))
+    }
+}
+
+fn is_prime(n: usize) -> bool {
+    if n < 2 {
+        return false;
+    }
+    let mut i = 2;
+    while i * i <= n {
+        if n.is_multiple_of(i) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+fn greatest_common_divisor(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { greatest_common_divisor(b, a % b) }
+}
@@ -1,28 +1,239 @@
 pub mod standard_weighted_load_balancer;
+pub mod least_connections_load_balancer;
+pub mod smooth_weighted_load_balancer;
+pub mod consistent_hash_load_balancer;
+pub mod maglev_load_balancer;
+pub mod power_of_two_choices_load_balancer;
+pub mod peak_ewma_load_balancer;
+pub mod hash;
+pub mod random;
+pub mod config_error;
+pub mod validation;
+pub mod registry;
 
-use std::path::Path;
-use super::server::socket_address::SocketAddress;
+use std::{any::Any, collections::HashMap, net::SocketAddr, path::Path, sync::Arc, time::Duration};
+use serde_json::Value;
+use config_error::ConfigError;
+use super::server::{
+    socket_address::{SocketAddress, SocketAddressError, IPV4_ERROR, IPV6_ERROR},
+    app::{ProxyMode, RetryConfig},
+    http::{ErrorPage, ErrorPages},
+    backend_state::BackendStates,
+    health_check::{HealthCheckConfig, HttpCheck},
+    dns::{DnsConfig, HostServer},
+    outlier_detection::OutlierDetectionConfig,
+    timeouts::TimeoutConfig,
+    shutdown::DEFAULT_DRAIN_TIMEOUT
+};
 
 // json keys
 static SERVERS_KEY: &str = "Servers";
 static SERVER_SOCADDR_KEY: &str = "Listen_to";
 static IPV4_KEY: &str = "ipv4";
+static IPV6_KEY: &str = "ipv6";
+static DUAL_STACK_KEY: &str = "dual_stack";
 static PORT_KEY: &str = "port";
 static WEIGHT_KEY: &str = "weight";
+static MODE_KEY: &str = "Mode";
+static ALGORITHM_KEY: &str = "Algorithm";
+static ALGORITHM_OPTIONS_KEY: &str = "Algorithm_options";
+static HEALTH_CHECK_KEY: &str = "Health_check";
+static INTERVAL_KEY: &str = "interval";
+static TIMEOUT_KEY: &str = "timeout";
+static RISE_KEY: &str = "rise";
+static FALL_KEY: &str = "fall";
+static HTTP_CHECK_KEY: &str = "http_check";
+static PATH_KEY: &str = "path";
+static METHOD_KEY: &str = "method";
+static HOST_KEY: &str = "host";
+static STATUS_KEY: &str = "status";
+static BODY_KEY: &str = "body";
+static OUTLIER_DETECTION_KEY: &str = "Outlier_detection";
+static CONSECUTIVE_FAILURES_KEY: &str = "consecutive_failures";
+static WINDOW_KEY: &str = "window";
+static BASE_EJECTION_TIME_KEY: &str = "base_ejection_time";
+static MAX_EJECTION_TIME_KEY: &str = "max_ejection_time";
+static MAX_EJECTION_PERCENT_KEY: &str = "max_ejection_percent";
+static RETRIES_KEY: &str = "Retries";
+static MAX_RETRIES_KEY: &str = "max_retries";
+static RETRY_ON_RESET_KEY: &str = "retry_on_reset";
+static TIMEOUTS_KEY: &str = "Timeouts";
+static CONNECT_KEY: &str = "connect";
+static CLIENT_FIRST_BYTE_KEY: &str = "client_first_byte";
+static BACKEND_FIRST_BYTE_KEY: &str = "backend_first_byte";
+static CLIENT_IDLE_KEY: &str = "client_idle";
+static BACKEND_IDLE_KEY: &str = "backend_idle";
+static LIFETIME_KEY: &str = "lifetime";
+static ERROR_PAGES_KEY: &str = "Error_pages";
+static DRAIN_TIMEOUT_KEY: &str = "Drain_timeout";
+static DNS_KEY: &str = "Dns";
+static ADMIN_KEY: &str = "Admin";
 
 // error messages
-static INCORRECT_PATH: &str = "The path of the file isn't correct";
-static INCORRECT_JSON_FORMAT: &str = "The json format isn't correct";
-static NO_SERVERS_KEY: &str = "The is no \"Servers\" key in the json";
-static NO_LISTEN_TO_KEY: &str = "The is no \"Listen_to\" key in the json";
-static NO_IPV4_KEY: &str = "The is no \"ipv4\" key in the json";
-static NO_PORT_KEY: &str = "The is no \"port\" key in the json";
-static NO_WEIGHT_KEY: &str = "The is no \"weight\" key in the json";
+static INCORRECT_JSON_ROOT: &str = "The json must be an object";
 static EMPTY_SERVERS_VEC: &str = "Empty Servers key";
+static BOTH_IP_KEYS: &str = "Must have only one of the \"ipv4\" and \"ipv6\" keys";
+static HOST_AND_IP_KEYS: &str = "Must have only one of the \"host\", \"ipv4\" and \"ipv6\" keys";
+static INVALID_HOSTNAME: &str = "Must be a hostname, e.g. \"api.internal\"";
+static NOT_AN_OBJECT: &str = "Must be an object";
+static NOT_AN_ARRAY: &str = "Must be an array";
+static NOT_A_STRING: &str = "Must be a string";
+static NOT_A_NUMBER: &str = "Must be a non negative integer";
+static NOT_A_POSITIVE_NUMBER: &str = "Must be a positive integer";
+static NOT_A_BOOLEAN: &str = "Must be a boolean";
+static INVALID_MODE: &str = "Must be \"tcp\" or \"http\"";
+static INVALID_ALGORITHM: &str = "Isn't a known algorithm";
+static INVALID_EJECTION_PERCENT: &str = "Must be at most 100";
+static INVALID_MAX_EJECTION_TIME: &str = "Must be at least \"base_ejection_time\"";
+static INVALID_ERROR_PAGE_STATUS: &str = "Must be a status code from 400 to 599";
+static INVALID_TOKEN: &str = "Must be a non empty string without spaces or control characters";
+static INVALID_STATUS_RANGE: &str = "Must be a [min, max] array of status codes from 100 to 999";
+static ADMIN_ON_LISTEN_TO: &str = "Must be different from \"Listen_to\"";
 
+// Max number of servers
+pub const MAX_SERVERS: usize = 256;
 
-/// An interface for all the load balancers implementation
-pub trait LoadBalancer {
+// Error messages of the load balancers
+pub static TOO_MANY_SERVERS: &str = "The number of servers is over 256";
+pub static ZERO_OR_NEGATIVE_SERVERS: &str = "The number of servers is 0 or a negative number";
+
+
+/// The load balancing algorithms that can be chosen in the json
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// "weighted_round_robin"
+    WeightedRoundRobin,
+    /// "least_connections"
+    LeastConnections,
+    /// "smooth_weighted_round_robin"
+    SmoothWeightedRoundRobin,
+    /// "consistent_hash"
+    ConsistentHash,
+    /// "maglev"
+    Maglev,
+    /// "power_of_two_choices"
+    PowerOfTwoChoices,
+    /// "peak_ewma"
+    PeakEwma
+}
+
+impl Algorithm {
+    /// Return the algorithm with the given name, if any
+    pub fn from_name(name: &str) -> Option<Self> {
+        registry::find(name).map(|registration| registration.algorithm)
+    }
+
+    /// Return the name of the algorithm, as written in the json
+    pub fn name(&self) -> &'static str {
+        registry::registration(*self).name
+    }
+}
+
+
+/// The configuration extracted from the json file
+#[derive(Debug)]
+pub struct Config {
+    /// socket address on which the server listens
+    pub listen_to: SocketAddress,
+    /// if true an IPv6 listener accepts also IPv4 connections
+    pub dual_stack: bool,
+    /// socket address of the admin API, None if disabled
+    pub admin: Option<SocketAddress>,
+    /// socket addresses of the servers with the relative weights
+    pub servers: Vec<(SocketAddress, usize)>,
+    /// servers with a hostname, resolved at startup and periodically
+    pub hosts: Vec<HostServer>,
+    /// configuration of the resolution of the hostnames
+    pub dns: DnsConfig,
+    /// how the accepted connections are proxied
+    pub mode: ProxyMode,
+    /// the load balancing algorithm
+    pub algorithm: Algorithm,
+    /// options of the load balancing algorithm, Null if there are none
+    pub algorithm_options: serde_json::Value,
+    /// configuration of the active health checks, None if disabled
+    pub health_check: Option<HealthCheckConfig>,
+    /// configuration of the passive outlier detection, None if disabled
+    pub outlier_detection: Option<OutlierDetectionConfig>,
+    /// configuration of the retries on another server
+    pub retries: RetryConfig,
+    /// timeouts of the client and server sides
+    pub timeouts: TimeoutConfig,
+    /// custom bodies of the error responses, HTTP mode only
+    pub error_pages: ErrorPages,
+    /// max time for the active connections to end on shutdown
+    pub drain_timeout: Duration
+}
+
+/// A server with its socket address and relative weight,
+/// after the position of its entry in the json
+pub type IndexedServer = (usize, (SocketAddress, usize));
+
+impl Config {
+    /// Return the positions in the json of the servers with an ip address,
+    /// the positions of the servers with a hostname are skipped
+    pub fn server_indexes(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.servers.len() + self.hosts.len())
+            .filter(|i| !self.hosts.iter().any(|host| host.index == *i))
+    }
+}
+
+
+/// Information about the connection (or request) to balance
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    /// socket address of the client, as returned by accept
+    client_address: SocketAddr,
+    /// states of the servers, None if every server is available
+    states: Option<Arc<BackendStates>>,
+    /// servers already tried for this connection (or request)
+    excluded: Vec<SocketAddress>
+}
+
+impl RequestContext {
+    pub fn new(client_address: SocketAddr) -> Self {
+        RequestContext {
+            client_address,
+            states: None,
+            excluded: Vec::new()
+        }
+    }
+
+    /// Return the context with the states of the servers,
+    /// used for skipping the unavailable ones
+    pub fn with_states(mut self, states: Arc<BackendStates>) -> Self {
+        self.states = Some(states);
+        self
+    }
+
+    /// Return the socket address of the client
+    pub fn client_address(&self) -> &SocketAddr {
+        &self.client_address
+    }
+
+    /// Return the states of the servers, if any
+    pub fn states(&self) -> Option<&Arc<BackendStates>> {
+        self.states.as_ref()
+    }
+
+    /// Exclude a server already tried, so a retry goes to another one
+    pub fn exclude(&mut self, socket_address: SocketAddress) {
+        self.excluded.push(socket_address);
+    }
+
+    /// Return true if the server can receive this connection.
+    /// Every balancer must skip the servers that aren't available.
+    pub fn is_available(&self, socket_address: &SocketAddress) -> bool {
+        !self.excluded.contains(socket_address)
+            && self.states.as_ref().is_none_or(|states| states.is_available(socket_address))
+    }
+}
+
+
+/// An interface for all the load balancers implementation.
+/// The trait is object safe, the server uses the balancer
+/// chosen in the json as a `dyn LoadBalancer`.
+pub trait LoadBalancer: Any + Send + Sync {
 
     /// Create and return a new instance of struct
     /// # Arguments
@@ -32,15 +243,69 @@ pub trait LoadBalancer {
     /// # Return
     /// 
     /// * A result with Self struct in a Box or an error string  
-    fn new(servers_number: usize) -> Result<Box<Self>, &'static str>;
+    fn new(servers_number: usize) -> Result<Box<Self>, &'static str>
+    where Self: Sized;
 
     /// Return the socket address of the next server.
-    /// The implementation of this operation must be thread safe.
+    /// The implementation of this operation must be thread safe
+    /// and must never return a server that isn't available.
+    /// A server with weight 0 is never chosen, unless every weight
+    /// is 0: then every server has weight 1.
+    /// # Arguments
+    ///
+    /// * `context` - information about the connection to balance
+    ///   and availability of the servers
     /// 
     /// # Return
     /// 
-    /// * A reference to a socket address
-    fn next_server(&self) -> &SocketAddress;
+    /// * A reference to a socket address, None if no server is available
+    fn next_server(&self, context: &RequestContext) -> Option<&SocketAddress>;
+
+    /// Set the options specific of the algorithm, read from the json.
+    /// Must be called before inserting the servers.
+    /// The default implementation accepts and ignores every option.
+    /// # Arguments
+    ///
+    /// * `options` - json object with the options, or Null
+    ///
+    /// # Return
+    ///
+    /// * A result with empty Ok or an error string
+    fn set_options(&mut self, _options: &serde_json::Value) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    /// Notify that a connection (or a request, in HTTP mode) sent to
+    /// the server returned by `next_server` is finished.
+    /// The default implementation does nothing, balancers that
+    /// track the in-flight connections must override it.
+    /// # Arguments
+    ///
+    /// * `socket_address` - the socket address returned by `next_server`
+    fn release(&self, _socket_address: &SocketAddress) {}
+
+    /// Report a latency measured by the proxy for the server returned
+    /// by `next_server`: the connect time in TCP mode, the time from the
+    /// connect to the response head in HTTP mode.
+    /// The default implementation does nothing, latency aware
+    /// balancers must override it.
+    /// # Arguments
+    ///
+    /// * `socket_address` - the socket address returned by `next_server`
+    /// * `latency` - the measured latency
+    fn report_latency(&self, _socket_address: &SocketAddress, _latency: Duration) {}
+
+    /// Take the state of the servers from the balancer of the old pool,
+    /// after a change of the servers from the admin API, the DNS or a reload.
+    /// The servers that are in both the balancers keep their in-flight
+    /// connections and statistics, and the connections still open on the
+    /// old balancer release the same state. Must be called after inserting
+    /// the servers. The default implementation does nothing, balancers
+    /// with a state for each server must override it.
+    /// # Arguments
+    ///
+    /// * `previous` - the balancer of the old pool, ignored if the algorithm is different
+    fn inherit(&mut self, _previous: &dyn LoadBalancer) {}
 
     /// Insert a new SocketAddress in the inner vector.
     /// Return an error if the len of vector is already 
@@ -74,6 +339,7 @@ pub trait LoadBalancer {
 /// # Return
 /// 
 /// * A result with Self struct or an error string  
+#[cfg(test)]
 pub fn load_balancer_factory<T>(servers_number: usize) -> Result<T, &'static str>
 where T: LoadBalancer + Sync + Send + 'static {
     match T::new(servers_number) {
@@ -84,78 +350,486 @@ where T: LoadBalancer + Sync + Send + 'static {
 
 
 /// Open the configuration json file and extract the servers data.
+/// The whole json is validated, so every invalid field is reported
+/// and not only the first one.
 /// # Arguments
-/// 
+///
 /// * `file_path` - the path of the configuration json file
-/// 
+///
 /// # Return
-/// 
-/// * Socket address of the server and a vector with tuples
-///   containing the socket address and the relative weight
-pub fn configure<'a>(file_path: &'a Path) -> (SocketAddress, Vec<(SocketAddress, usize)>) {
+///
+/// * The configuration, with the socket address of the server and
+///   a vector with tuples containing the socket address and the
+///   relative weight, or all the errors found
+pub fn configure(file_path: &Path) -> Result<Config, Vec<ConfigError>> {
     let file = std::fs::OpenOptions::new()
             .write(false)
             .read(true)
             .open(file_path)
-            .expect(INCORRECT_PATH);
+            .map_err(|error| vec![ConfigError::Io {
+                path: String::new(),
+                file: file_path.to_path_buf(),
+                error
+            }])?;
 
-    let json: serde_json::Value = serde_json::from_reader(file).expect(INCORRECT_JSON_FORMAT);
+    let json: serde_json::Value = serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|error| vec![ConfigError::Json(error)])?;
+    if !json.is_object() {
+        return Err(vec![ConfigError::invalid("", INCORRECT_JSON_ROOT)]);
+    }
 
-    let listen_to = json[SERVER_SOCADDR_KEY].as_object().expect(NO_LISTEN_TO_KEY);
-    let server_socket_address = match SocketAddress::new(
-        listen_to.get(IPV4_KEY).expect(NO_IPV4_KEY).as_str().expect(NO_IPV4_KEY).to_string(),
-        listen_to.get(PORT_KEY).expect(NO_PORT_KEY).as_str().expect(NO_IPV4_KEY).to_string()
-    ) {
-        Ok(server_socket_address) => server_socket_address,
-        Err(e) => panic!("{e}")
+    let mut v = Validator::default();
+
+    let listen_to = v.required(&json, SERVER_SOCADDR_KEY, SERVER_SOCADDR_KEY);
+    let server_socket_address = listen_to
+        .and_then(|listen_to| v.socket_address(listen_to, SERVER_SOCADDR_KEY));
+    // optional, an IPv6 listener accepts also IPv4 connections by default
+    let dual_stack = listen_to
+        .and_then(|listen_to| v.boolean_at(listen_to, DUAL_STACK_KEY, SERVER_SOCADDR_KEY))
+        .unwrap_or(true);
+
+    // optional, the admin API is disabled if missing
+    let admin = json.get(ADMIN_KEY)
+        .and_then(|admin| v.socket_address(admin, ADMIN_KEY));
+    if admin.is_some() && admin == server_socket_address {
+        v.invalid::<()>(ADMIN_KEY, ADMIN_ON_LISTEN_TO);
+    }
+
+    let mut servers = Vec::new();
+    let mut hosts = Vec::new();
+    let mut http_checks = HashMap::new();
+    let servers_arr = v.required(&json, SERVERS_KEY, SERVERS_KEY)
+        .and_then(|servers_arr| v.array(servers_arr, SERVERS_KEY));
+    if servers_arr.is_some_and(|servers_arr| servers_arr.is_empty()) {
+        v.invalid::<()>(SERVERS_KEY, EMPTY_SERVERS_VEC);
+    }
+    for (i, element) in servers_arr.into_iter().flatten().enumerate() {
+        let path = format!("{SERVERS_KEY}[{i}]");
+        if v.object(element, &path).is_none() {
+            continue;
+        }
+        // a server has an ip address or a hostname
+        let (socket_addr, host) = match element.get(HOST_KEY) {
+            None => (v.socket_address(element, &path), None),
+            Some(_) => (None, v.host(element, &path))
+        };
+        let weight_path = join(&path, WEIGHT_KEY);
+        let weight = v.required(element, WEIGHT_KEY, &weight_path)
+            .and_then(|weight| v.number(weight, &weight_path));
+
+        // optional, the server is checked with a TCP connect if missing
+        let http_check = element.get(HTTP_CHECK_KEY)
+            .and_then(|http_check| v.http_check(http_check, &join(&path, HTTP_CHECK_KEY)));
+
+        match (socket_addr, host, weight) {
+            (Some(socket_addr), _, Some(weight)) => {
+                if let Some(http_check) = http_check {
+                    http_checks.insert(socket_addr.clone(), http_check);
+                }
+                servers.push((socket_addr, weight as usize));
+            },
+            (_, Some((host, port)), Some(weight)) => {
+                hosts.push(HostServer { index: i, host, port, weight: weight as usize, http_check });
+            },
+            _ => ()
+        }
+    }
+
+    // optional, the default is the plain TCP proxy
+    let mode = match json.get(MODE_KEY).map(|mode| mode.as_str()) {
+        None | Some(Some("tcp")) => ProxyMode::Tcp,
+        Some(Some("http")) => ProxyMode::Http,
+        Some(_) => v.invalid(MODE_KEY, INVALID_MODE).unwrap_or(ProxyMode::Tcp)
     };
 
-    let servers_arr = json[SERVERS_KEY].as_array().expect(NO_SERVERS_KEY);
-    if servers_arr.is_empty() {
-        panic!("{EMPTY_SERVERS_VEC}");
+    // optional, the default is the weighted round robin
+    let algorithm = match json.get(ALGORITHM_KEY) {
+        None => Algorithm::WeightedRoundRobin,
+        Some(algorithm) => algorithm.as_str()
+            .and_then(Algorithm::from_name)
+            .or_else(|| v.invalid(ALGORITHM_KEY, INVALID_ALGORITHM))
+            .unwrap_or(Algorithm::WeightedRoundRobin)
+    };
+
+    // optional, every algorithm has its own options
+    let algorithm_options = match json.get(ALGORITHM_OPTIONS_KEY) {
+        None => serde_json::Value::Null,
+        Some(options) if options.is_object() => options.clone(),
+        Some(_) => v.invalid(ALGORITHM_OPTIONS_KEY, NOT_AN_OBJECT).unwrap_or_default()
+    };
+
+    // optional, the health checks are disabled if missing,
+    // unless a server has an HTTP check
+    let mut health_check = json.get(HEALTH_CHECK_KEY).map(|health_check| {
+        v.object(health_check, HEALTH_CHECK_KEY);
+        let path = HEALTH_CHECK_KEY;
+        let default = HealthCheckConfig::default();
+        HealthCheckConfig {
+            interval: v.millis_at(health_check, INTERVAL_KEY, path).unwrap_or(default.interval),
+            timeout: v.millis_at(health_check, TIMEOUT_KEY, path).unwrap_or(default.timeout),
+            rise: v.positive_at(health_check, RISE_KEY, path).map_or(default.rise, |n| n as usize),
+            fall: v.positive_at(health_check, FALL_KEY, path).map_or(default.fall, |n| n as usize),
+            http_checks: HashMap::new()
+        }
+    });
+    if !http_checks.is_empty() || hosts.iter().any(|host| host.http_check.is_some()) {
+        health_check.get_or_insert_with(HealthCheckConfig::default).http_checks = http_checks;
     }
 
-    let servers: Vec<(SocketAddress, usize)> = servers_arr.iter().map(|element| {
-        let socket_addr = match SocketAddress::new(
-            element[IPV4_KEY].as_str().expect(NO_IPV4_KEY).to_string(), 
-            element[PORT_KEY].as_str().expect(NO_PORT_KEY).to_string()
-        ) {
-            Ok(socket_addr) => socket_addr,
-            Err(e) => panic!("{e}")
+    // optional, the hostnames are resolved again every 30 seconds if missing
+    let dns = match json.get(DNS_KEY) {
+        None => DnsConfig::default(),
+        Some(dns) => {
+            v.object(dns, DNS_KEY);
+            DnsConfig {
+                interval: v.millis_at(dns, INTERVAL_KEY, DNS_KEY).unwrap_or(DnsConfig::default().interval)
+            }
+        }
+    };
+
+    // optional, the outlier detection is disabled if missing
+    let outlier_detection = json.get(OUTLIER_DETECTION_KEY).map(|outlier_detection| {
+        v.object(outlier_detection, OUTLIER_DETECTION_KEY);
+        let path = OUTLIER_DETECTION_KEY;
+        let default = OutlierDetectionConfig::default();
+        let config = OutlierDetectionConfig {
+            consecutive_failures: v.positive_at(outlier_detection, CONSECUTIVE_FAILURES_KEY, path)
+                .map_or(default.consecutive_failures, |n| n as usize),
+            window: v.millis_at(outlier_detection, WINDOW_KEY, path).unwrap_or(default.window),
+            base_ejection_time: v.millis_at(outlier_detection, BASE_EJECTION_TIME_KEY, path)
+                .unwrap_or(default.base_ejection_time),
+            max_ejection_time: v.millis_at(outlier_detection, MAX_EJECTION_TIME_KEY, path)
+                .unwrap_or(default.max_ejection_time),
+            max_ejection_percent: v.positive_at(outlier_detection, MAX_EJECTION_PERCENT_KEY, path)
+                .map_or(default.max_ejection_percent, |n| n as usize)
         };
+        if config.max_ejection_percent > 100 {
+            v.invalid::<()>(&join(path, MAX_EJECTION_PERCENT_KEY), INVALID_EJECTION_PERCENT);
+        }
+        if config.max_ejection_time < config.base_ejection_time {
+            v.invalid::<()>(&join(path, MAX_EJECTION_TIME_KEY), INVALID_MAX_EJECTION_TIME);
+        }
+        config
+    });
+
+    // optional, the retries are disabled if missing
+    let retries = match json.get(RETRIES_KEY) {
+        None => RetryConfig::default(),
+        Some(retries) => {
+            v.object(retries, RETRIES_KEY);
+            let path = RETRIES_KEY;
+            let default = RetryConfig::default();
+            RetryConfig {
+                max_retries: v.number_at(retries, MAX_RETRIES_KEY, path)
+                    .map_or(default.max_retries, |n| n as usize),
+                retry_on_reset: v.boolean_at(retries, RETRY_ON_RESET_KEY, path)
+                    .unwrap_or(default.retry_on_reset)
+            }
+        }
+    };
+
+    // optional, every missing timeout is disabled
+    let timeouts = match json.get(TIMEOUTS_KEY) {
+        None => TimeoutConfig::default(),
+        Some(timeouts) => {
+            v.object(timeouts, TIMEOUTS_KEY);
+            let path = TIMEOUTS_KEY;
+            TimeoutConfig {
+                connect: v.millis_at(timeouts, CONNECT_KEY, path),
+                client_first_byte: v.millis_at(timeouts, CLIENT_FIRST_BYTE_KEY, path),
+                backend_first_byte: v.millis_at(timeouts, BACKEND_FIRST_BYTE_KEY, path),
+                client_idle: v.millis_at(timeouts, CLIENT_IDLE_KEY, path),
+                backend_idle: v.millis_at(timeouts, BACKEND_IDLE_KEY, path),
+                lifetime: v.millis_at(timeouts, LIFETIME_KEY, path)
+            }
+        }
+    };
+
+    // optional, the error responses have a plain text body if missing;
+    // the paths are relative to the directory of the configuration file
+    let mut error_pages = ErrorPages::default();
+    let pages = json.get(ERROR_PAGES_KEY)
+        .and_then(|pages| v.object(pages, ERROR_PAGES_KEY));
+    for (status, page_path) in pages.into_iter().flatten() {
+        let path = join(ERROR_PAGES_KEY, status);
+        let status = status.parse::<u16>().ok()
+            .filter(|status| (400..=599).contains(status))
+            .or_else(|| v.invalid(&path, INVALID_ERROR_PAGE_STATUS));
+        let page_path = v.string(page_path, &path)
+            .map(|page_path| file_path.parent().unwrap_or(Path::new("")).join(page_path));
+        if let (Some(status), Some(page_path)) = (status, page_path) {
+            match ErrorPage::from_file(&page_path) {
+                Ok(page) => error_pages.insert(status, page),
+                Err(error) => v.errors.push(ConfigError::Io { path, file: page_path, error })
+            }
+        }
+    }
+
+    // optional, 0 aborts the connections immediately
+    let drain_timeout = json.get(DRAIN_TIMEOUT_KEY)
+        .and_then(|timeout| v.number(timeout, DRAIN_TIMEOUT_KEY))
+        .map_or(DEFAULT_DRAIN_TIMEOUT, Duration::from_millis);
+
+    let (Some(server_socket_address), true) = (server_socket_address, v.errors.is_empty()) else {
+        return Err(v.errors);
+    };
+    Ok(Config {
+        listen_to: server_socket_address,
+        dual_stack,
+        admin,
+        servers,
+        hosts,
+        dns,
+        mode,
+        algorithm,
+        algorithm_options,
+        health_check,
+        outlier_detection,
+        retries,
+        timeouts,
+        error_pages,
+        drain_timeout
+    })
+}
 
-        let weight = element[WEIGHT_KEY].as_u64().expect(NO_WEIGHT_KEY);
 
-        (socket_addr, weight as usize)
+/// Return the json path of a key inside the value at `path`
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Return true if the string is a hostname: dot separated labels
+/// of letters, digits and hyphens, not starting or ending with a hyphen
+fn is_hostname(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+    !host.is_empty() && host.len() <= 253 && host.split('.').all(|label| {
+        !label.is_empty() && label.len() <= 63
+            && !label.starts_with('-') && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
     })
-    .collect();
+}
+
+
+/// Checks the values of the json, collecting the errors
+/// so that they can be reported all together
+#[derive(Debug, Default)]
+struct Validator {
+    errors: Vec<ConfigError>
+}
+
+impl Validator {
+    /// Record an invalid value.
+    /// # Return
+    ///
+    /// * Always None, so it can be used in place of the value
+    fn invalid<T>(&mut self, path: &str, reason: &'static str) -> Option<T> {
+        self.errors.push(ConfigError::invalid(path, reason));
+        None
+    }
+
+    /// Return the value of a required key, or record it as missing.
+    /// # Arguments
+    ///
+    /// * `parent` - the object containing the key
+    /// * `key` - the required key
+    /// * `path` - the json path of the key
+    fn required<'a>(&mut self, parent: &'a Value, key: &str, path: &str) -> Option<&'a Value> {
+        let value = parent.get(key);
+        if value.is_none() {
+            self.errors.push(ConfigError::MissingKey { path: path.to_string() });
+        }
+        value
+    }
+
+    fn object<'a>(&mut self, value: &'a Value, path: &str) -> Option<&'a serde_json::Map<String, Value>> {
+        value.as_object().or_else(|| self.invalid(path, NOT_AN_OBJECT))
+    }
+
+    fn array<'a>(&mut self, value: &'a Value, path: &str) -> Option<&'a Vec<Value>> {
+        value.as_array().or_else(|| self.invalid(path, NOT_AN_ARRAY))
+    }
+
+    fn string<'a>(&mut self, value: &'a Value, path: &str) -> Option<&'a str> {
+        value.as_str().or_else(|| self.invalid(path, NOT_A_STRING))
+    }
+
+    fn number(&mut self, value: &Value, path: &str) -> Option<u64> {
+        value.as_u64().or_else(|| self.invalid(path, NOT_A_NUMBER))
+    }
+
+    /// Return the optional non negative integer of the key
+    fn number_at(&mut self, parent: &Value, key: &str, path: &str) -> Option<u64> {
+        parent.get(key).and_then(|value| self.number(value, &join(path, key)))
+    }
+
+    /// Return the optional positive integer of the key
+    fn positive_at(&mut self, parent: &Value, key: &str, path: &str) -> Option<u64> {
+        parent.get(key).and_then(|value| match value.as_u64() {
+            Some(n) if n > 0 => Some(n),
+            _ => self.invalid(&join(path, key), NOT_A_POSITIVE_NUMBER)
+        })
+    }
+
+    /// Return the optional positive milliseconds of the key
+    fn millis_at(&mut self, parent: &Value, key: &str, path: &str) -> Option<Duration> {
+        self.positive_at(parent, key, path).map(Duration::from_millis)
+    }
+
+    /// Return the optional boolean of the key
+    fn boolean_at(&mut self, parent: &Value, key: &str, path: &str) -> Option<bool> {
+        parent.get(key).and_then(|value| {
+            value.as_bool().or_else(|| self.invalid(&join(path, key), NOT_A_BOOLEAN))
+        })
+    }
 
-    (server_socket_address, servers)
+    /// Return the optional string of the key
+    fn string_at<'a>(&mut self, parent: &'a Value, key: &str, path: &str) -> Option<&'a str> {
+        parent.get(key).and_then(|value| self.string(value, &join(path, key)))
+    }
+
+    /// Return the optional string of the key, written in a request head as it is
+    fn token_at(&mut self, parent: &Value, key: &str, path: &str) -> Option<String> {
+        self.string_at(parent, key, path).and_then(|value| {
+            if value.is_empty() || value.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return self.invalid(&join(path, key), INVALID_TOKEN);
+            }
+            Some(value.to_string())
+        })
+    }
+
+    /// Return the socket address of the "ipv4" (or "ipv6") and "port" keys.
+    /// # Arguments
+    ///
+    /// * `json` - the object containing the keys
+    /// * `path` - the json path of the object
+    fn socket_address(&mut self, json: &Value, path: &str) -> Option<SocketAddress> {
+        self.object(json, path)?;
+        // the ipv4 key is the required one, the ipv6 key can replace it
+        let (ip_key, family_error) = match (json.get(IPV4_KEY), json.get(IPV6_KEY)) {
+            (Some(_), Some(_)) => return self.invalid(path, BOTH_IP_KEYS),
+            (None, Some(_)) => (IPV6_KEY, IPV6_ERROR),
+            _ => (IPV4_KEY, IPV4_ERROR)
+        };
+        let ip_path = join(path, ip_key);
+        let port_path = join(path, PORT_KEY);
+        let ip = self.required(json, ip_key, &ip_path)
+            .and_then(|ip| self.string(ip, &ip_path));
+        let port = self.required(json, PORT_KEY, &port_path)
+            .and_then(|port| self.string(port, &port_path));
+        let (ip, port) = (ip?, port?);
+        if ip.contains(':') != (ip_key == IPV6_KEY) {
+            return self.invalid(&ip_path, family_error);
+        }
+
+        match SocketAddress::from_parts(ip, port) {
+            Ok(socket_address) => Some(socket_address),
+            Err(e @ SocketAddressError::InvalidPort) => self.invalid(&port_path, e.reason()),
+            Err(e) => self.invalid(&ip_path, e.reason())
+        }
+    }
+
+    /// Return the hostname of the "host" key and the port of the "port" key.
+    /// # Arguments
+    ///
+    /// * `json` - the object containing the keys
+    /// * `path` - the json path of the object
+    fn host(&mut self, json: &Value, path: &str) -> Option<(String, u16)> {
+        if json.get(IPV4_KEY).is_some() || json.get(IPV6_KEY).is_some() {
+            return self.invalid(path, HOST_AND_IP_KEYS);
+        }
+        let host_path = join(path, HOST_KEY);
+        let port_path = join(path, PORT_KEY);
+        let host = self.required(json, HOST_KEY, &host_path)
+            .and_then(|host| self.string(host, &host_path))
+            .and_then(|host| if is_hostname(host) {
+                Some(host)
+            } else {
+                self.invalid(&host_path, INVALID_HOSTNAME)
+            });
+        let port = self.required(json, PORT_KEY, &port_path)
+            .and_then(|port| self.string(port, &port_path))
+            .and_then(|port| match SocketAddress::parse_port(port) {
+                Ok(port) => Some(port),
+                Err(e) => self.invalid(&port_path, e.reason())
+            });
+        Some((host?.to_string(), port?))
+    }
+
+    /// Parse the optional HTTP check of a server, every key is optional.
+    /// # Arguments
+    ///
+    /// * `json` - the value of the "http_check" key
+    /// * `path` - the json path of the value
+    ///
+    /// # Return
+    ///
+    /// * The HTTP check, with the defaults for the missing keys
+    fn http_check(&mut self, json: &Value, path: &str) -> Option<HttpCheck> {
+        self.object(json, path)?;
+        let default = HttpCheck::default();
+
+        let status = json.get(STATUS_KEY).and_then(|status| {
+            let range: Option<Vec<u16>> = status.as_array().and_then(|codes| codes.iter()
+                .map(|code| code.as_u64()
+                    .filter(|code| (100..=999).contains(code))
+                    .map(|code| code as u16))
+                .collect());
+            match range.as_deref() {
+                Some(&[min, max]) if min <= max => Some((min, max)),
+                _ => self.invalid(&join(path, STATUS_KEY), INVALID_STATUS_RANGE)
+            }
+        });
+
+        Some(HttpCheck {
+            path: self.token_at(json, PATH_KEY, path).unwrap_or(default.path),
+            method: self.token_at(json, METHOD_KEY, path).unwrap_or(default.method),
+            host: self.token_at(json, HOST_KEY, path),
+            status: status.unwrap_or(default.status),
+            body: self.string_at(json, BODY_KEY, path).map(str::to_string),
+            timeout: self.millis_at(json, TIMEOUT_KEY, path)
+        })
+    }
 }
 
 
-/// Create, fill and return the load balancer generic struct.
+/// Create and fill the load balancer of the algorithm chosen at runtime.
 /// # Arguments
-/// 
-/// * `servers` - vector with socket addresses and relative weights
-/// 
-/// # Generics
-/// 
-/// * `T` - load balancer type
-/// 
+///
+/// * `algorithm` - the algorithm, its implementation is taken from the registry
+/// * `servers` - vector with socket addresses and relative weights,
+///   each with its position in the json for the errors
+/// * `options` - options of the algorithm, or Null
+///
 /// # Return
-/// 
-/// * A load balancer of type T
-pub fn create_and_fill_the_balancer<T>(servers: Vec<(SocketAddress, usize)>) -> T
-where T: LoadBalancer + Sync + Send + 'static {
-    let mut balancer = match load_balancer_factory::<T>(servers.len()) {
-        Ok(balancer) => balancer,
-        Err(e) => panic!("{e}")
-    };
-    for (socket_address, weight) in servers {
-        balancer.insert_socket_address(socket_address, weight).unwrap_or_else(|error| {
-            panic!("{error}")
-        });
+///
+/// * The load balancer, or the error of the first
+///   server or option refused by the balancer
+pub fn create_balancer(
+    algorithm: Algorithm,
+    servers: Vec<IndexedServer>,
+    options: &serde_json::Value
+) -> Result<Box<dyn LoadBalancer>, ConfigError> {
+    let mut balancer = (registry::registration(algorithm).constructor)(servers.len())
+        .map_err(|reason| ConfigError::invalid(SERVERS_KEY, reason))?;
+    fill_the_balancer(balancer.as_mut(), servers, options)?;
+    Ok(balancer)
+}
+
+/// Set the options of the algorithm, then insert the servers
+fn fill_the_balancer(
+    balancer: &mut dyn LoadBalancer,
+    servers: Vec<IndexedServer>,
+    options: &serde_json::Value
+) -> Result<(), ConfigError> {
+    balancer.set_options(options)
+        .map_err(|reason| ConfigError::invalid(ALGORITHM_OPTIONS_KEY, reason))?;
+    for (i, (socket_address, weight)) in servers {
+        balancer.insert_socket_address(socket_address, weight)
+            .map_err(|reason| ConfigError::invalid(format!("{SERVERS_KEY}[{i}]"), reason))?;
     }
-    balancer
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use std::{
+    any::Any,
+    sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex},
+    time::{Duration, Instant}
+};
+use crate::{
+    server::socket_address::*,
+    balancers::{
+        LoadBalancer,
+        RequestContext,
+        MAX_SERVERS,
+        TOO_MANY_SERVERS,
+        ZERO_OR_NEGATIVE_SERVERS
+    }
+};
+
+// Default decay window of the moving average
+pub const DEFAULT_DECAY: Duration = Duration::from_secs(10);
+// Latency assumed for a server before the first report
+pub const INITIAL_LATENCY: Duration = Duration::from_millis(1);
+// Json key of the option for the decay window, in milliseconds
+static DECAY_KEY: &str = "decay";
+
+// Error messages
+pub static INVALID_DECAY: &str = "The Peak EWMA decay must be a positive number of milliseconds";
+
+/// The moving average of the latency of a server
+#[derive(Debug)]
+struct Ewma {
+    /// the average, in nanoseconds
+    latency: f64,
+    /// when the average was updated
+    updated_at: Instant
+}
+
+impl Ewma {
+    /// Add a latency to the average: a peak replaces it at once,
+    /// a lower latency is averaged with a weight that depends on
+    /// the time passed since the last update.
+    /// # Arguments
+    ///
+    /// * `latency` - the latency, in nanoseconds
+    /// * `now` - the time of the update
+    /// * `decay` - the decay window, in nanoseconds
+    fn update(&mut self, latency: f64, now: Instant, decay: f64) {
+        if latency > self.latency {
+            self.latency = latency;
+        } else {
+            let elapsed = now.saturating_duration_since(self.updated_at).as_nanos() as f64;
+            let w = (-elapsed / decay).exp();
+            self.latency = self.latency * w + latency * (1.0 - w);
+        }
+        self.updated_at = now;
+    }
+}
+
+/// A server with its weight, the number of in-flight
+/// requests and the moving average of its latency.
+/// The requests and the average are shared with the balancers of the next pools.
+#[derive(Debug)]
+struct Backend {
+    socket_address: SocketAddress,
+    weight: usize,
+    active: Arc<AtomicUsize>,
+    ewma: Arc<Mutex<Ewma>>
+}
+
+impl Backend {
+    /// Return the cost of the server: latency × (in-flight requests + 1) / weight.
+    /// The average decays towards 0 for the time passed since the
+    /// last update, so a server without traffic is tried again.
+    fn cost(&self, weight: usize, now: Instant, decay: f64) -> f64 {
+        let latency = {
+            let mut ewma = self.ewma.lock().unwrap();
+            ewma.update(0.0, now, decay);
+            // at least 1 nanosecond, so the in-flight requests still count
+            ewma.latency.max(1.0)
+        };
+        let active = self.active.load(Ordering::Relaxed) as f64;
+        latency * (active + 1.0) / weight as f64
+    }
+}
+
+/// The main struct for storing the Socket Address of each server
+/// and perform Peak EWMA Algorithm.
+/// Every server has an exponentially weighted moving average of the
+/// latencies reported by the proxy. A latency higher than the average
+/// replaces it at once (peak), lower ones are averaged with a weight that
+/// depends on the time passed, so the average forgets old values in about
+/// "decay" time. Without reports the average decays towards 0 while
+/// it's read. The chosen server is the one with the lowest cost,
+/// that is latency × (in-flight requests + 1) / weight.
+#[derive(Debug)]
+pub struct PeakEwmaLB {
+    /// stores the Socket Address of each server
+    backends: Vec<Backend>,
+    /// decay window of the moving average, in nanoseconds
+    decay: f64,
+    /// true if every weight is 0, then every server has weight 1
+    all_zero: bool
+}
+
+impl PeakEwmaLB {
+
+    /// Return the len of the inner vector
+    #[allow(dead_code)]
+    pub fn n_of_servers(&self) -> usize {
+        self.backends.len()
+    }
+
+    /// Return the moving average of the latency of a server
+    #[allow(dead_code)]
+    pub fn latency(&self, socket_address: &SocketAddress) -> Option<Duration> {
+        self.backends.iter()
+            .find(|backend| backend.socket_address == *socket_address)
+            .map(|backend| Duration::from_nanos(backend.ewma.lock().unwrap().latency as u64))
+    }
+
+    /// Set the decay window of the moving average
+    pub fn set_decay(&mut self, decay: Duration) -> Result<(), &'static str> {
+        if decay.is_zero() {
+            return Err(INVALID_DECAY);
+        }
+        self.decay = decay.as_nanos() as f64;
+        Ok(())
+    }
+
+}
+
+impl LoadBalancer for PeakEwmaLB {
+
+    fn new(servers_number: usize) -> Result<Box<Self>, &'static str> {
+        if servers_number > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        if servers_number == 0 {
+            return Err(ZERO_OR_NEGATIVE_SERVERS);
+        }
+        Ok(Box::new(PeakEwmaLB {
+            backends: Vec::with_capacity(servers_number),
+            decay: DEFAULT_DECAY.as_nanos() as f64,
+            all_zero: true
+        }))
+    }
+
+    fn set_options(&mut self, options: &serde_json::Value) -> Result<(), &'static str> {
+        match options.get(DECAY_KEY) {
+            None => Ok(()),
+            Some(decay) => match decay.as_u64() {
+                Some(decay) => self.set_decay(Duration::from_millis(decay)),
+                None => Err(INVALID_DECAY)
+            }
+        }
+    }
+
+    fn insert_socket_address(&mut self, socket_address: SocketAddress, weight: usize) -> Result<(), &'static str> {
+        if self.backends.len() + 1 > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        self.backends.push(Backend {
+            socket_address,
+            weight,
+            active: Arc::new(AtomicUsize::new(0)),
+            ewma: Arc::new(Mutex::new(Ewma {
+                latency: INITIAL_LATENCY.as_nanos() as f64,
+                updated_at: Instant::now()
+            }))
+        });
+        self.all_zero &= weight == 0;
+        Ok(())
+    }
+
+    fn next_server(&self, context: &RequestContext) -> Option<&SocketAddress> {
+        // servers with weight 0 or not available are never chosen,
+        // unless all the weights are 0
+        let weight = |backend: &Backend| if self.all_zero { 1 } else { backend.weight };
+        let now = Instant::now();
+        let (_, backend) = self.backends.iter()
+            .filter(|backend| weight(backend) > 0 && context.is_available(&backend.socket_address))
+            .map(|backend| (backend.cost(weight(backend), now, self.decay), backend))
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))?;
+
+        backend.active.fetch_add(1, Ordering::Relaxed);
+        Some(&backend.socket_address)
+    }
+
+    fn release(&self, socket_address: &SocketAddress) {
+        let backend = self.backends.iter().find(|backend| {
+            backend.socket_address == *socket_address
+                && backend.active.load(Ordering::Relaxed) > 0
+        });
+        if let Some(backend) = backend {
+            // never goes below 0, even with concurrent releases
+            let _ = backend.active.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                n.checked_sub(1)
+            });
+        }
+    }
+
+    fn report_latency(&self, socket_address: &SocketAddress, latency: Duration) {
+        let latency = latency.as_nanos() as f64;
+        let now = Instant::now();
+        for backend in self.backends.iter().filter(|b| b.socket_address == *socket_address) {
+            backend.ewma.lock().unwrap().update(latency, now, self.decay);
+        }
+    }
+
+    fn inherit(&mut self, previous: &dyn LoadBalancer) {
+        if let Some(previous) = (previous as &dyn Any).downcast_ref::<Self>() {
+            for backend in self.backends.iter_mut() {
+                let old = previous.backends.iter().find(|old| old.socket_address == backend.socket_address);
+                if let Some(old) = old {
+                    backend.active = Arc::clone(&old.active);
+                    backend.ewma = Arc::clone(&old.ewma);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::{any::Any, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+use crate::{
+    server::socket_address::*,
+    balancers::{
+        random::random_below,
+        LoadBalancer,
+        RequestContext,
+        MAX_SERVERS,
+        TOO_MANY_SERVERS,
+        ZERO_OR_NEGATIVE_SERVERS
+    }
+};
+
+/// A server with its weight and the number of in-flight connections
+#[derive(Debug)]
+struct Backend {
+    socket_address: SocketAddress,
+    weight: usize,
+    /// shared with the balancers of the next pools
+    active: Arc<AtomicUsize>
+}
+
+/// The main struct for storing the Socket Address of each server
+/// and perform Power of Two Choices Algorithm (P2C).
+/// Two servers are sampled at random, with probability proportional
+/// to their weight, and the one with fewer in-flight connections is
+/// chosen. Close to least connections without any lock.
+#[derive(Debug)]
+pub struct PowerOfTwoChoicesLB {
+    /// stores the Socket Address of each server
+    backends: Vec<Backend>,
+    /// cumulative weights, used for the weighted sampling
+    cumulative_weights: Vec<usize>
+}
+
+impl PowerOfTwoChoicesLB {
+
+    /// Return the len of the inner vector
+    #[allow(dead_code)]
+    pub fn n_of_servers(&self) -> usize {
+        self.backends.len()
+    }
+
+    /// Return the number of in-flight connections of a server
+    #[allow(dead_code)]
+    pub fn active_connections(&self, socket_address: &SocketAddress) -> usize {
+        self.backends.iter()
+            .filter(|backend| backend.socket_address == *socket_address)
+            .map(|backend| backend.active.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Return the indexes of two different random servers, chosen with
+    /// probability proportional to their weight. The second one is sampled
+    /// from the slots of the other servers, so it's the same as the first
+    /// one only if that is the only server with a weight.
+    fn sample_two(&self) -> (usize, usize) {
+        let len = self.backends.len();
+        let total_weight = self.cumulative_weights.last().copied().unwrap_or(0);
+
+        // all the weights are 0, uniform sampling
+        if total_weight == 0 {
+            let first = random_below(len as u64) as usize;
+            if len == 1 {
+                return (first, first);
+            }
+            let mut second = random_below(len as u64 - 1) as usize;
+            if second >= first {
+                second += 1;
+            }
+            return (first, second);
+        }
+
+        let first = self.server_of_slot(random_below(total_weight as u64) as usize);
+        let start = if first == 0 { 0 } else { self.cumulative_weights[first - 1] };
+        let weight = self.cumulative_weights[first] - start;
+        if weight == total_weight {
+            return (first, first);
+        }
+        // the slots of the first server are skipped
+        let mut slot = random_below((total_weight - weight) as u64) as usize;
+        if slot >= start {
+            slot += weight;
+        }
+        (first, self.server_of_slot(slot))
+    }
+
+    /// Return the index of the server that owns a slot of the weights
+    fn server_of_slot(&self, slot: usize) -> usize {
+        self.cumulative_weights.partition_point(|end| *end <= slot)
+    }
+
+}
+
+impl LoadBalancer for PowerOfTwoChoicesLB {
+
+    fn new(servers_number: usize) -> Result<Box<Self>, &'static str> {
+        if servers_number > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        if servers_number == 0 {
+            return Err(ZERO_OR_NEGATIVE_SERVERS);
+        }
+        Ok(Box::new(PowerOfTwoChoicesLB {
+            backends: Vec::with_capacity(servers_number),
+            cumulative_weights: Vec::with_capacity(servers_number)
+        }))
+    }
+
+    fn insert_socket_address(&mut self, socket_address: SocketAddress, weight: usize) -> Result<(), &'static str> {
+        if self.backends.len() + 1 > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        let total_weight = self.cumulative_weights.last().copied().unwrap_or(0);
+        self.cumulative_weights.push(total_weight + weight);
+        self.backends.push(Backend {
+            socket_address,
+            weight,
+            active: Arc::new(AtomicUsize::new(0))
+        });
+        Ok(())
+    }
+
+    fn next_server(&self, context: &RequestContext) -> Option<&SocketAddress> {
+        let (first, second) = self.sample_two();
+
+        let all_zero = self.cumulative_weights.last() == Some(&0);
+        let is_candidate = |backend: &&Backend| {
+            (backend.weight > 0 || all_zero) && context.is_available(&backend.socket_address)
+        };
+        let active = |backend: &&Backend| backend.active.load(Ordering::Relaxed);
+
+        let sampled = [&self.backends[first], &self.backends[second]];
+        let backend = match sampled.into_iter().filter(is_candidate).min_by_key(active) {
+            Some(backend) => backend,
+            // both the sampled servers aren't available,
+            // the least loaded among the available ones
+            None => self.backends.iter().filter(is_candidate).min_by_key(active)?
+        };
+        backend.active.fetch_add(1, Ordering::Relaxed);
+        Some(&backend.socket_address)
+    }
+
+    fn release(&self, socket_address: &SocketAddress) {
+        let backend = self.backends.iter().find(|backend| {
+            backend.socket_address == *socket_address
+                && backend.active.load(Ordering::Relaxed) > 0
+        });
+        if let Some(backend) = backend {
+            // never goes below 0, even with concurrent releases
+            let _ = backend.active.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                n.checked_sub(1)
+            });
+        }
+    }
+
+    fn inherit(&mut self, previous: &dyn LoadBalancer) {
+        if let Some(previous) = (previous as &dyn Any).downcast_ref::<Self>() {
+            for backend in self.backends.iter_mut() {
+                let old = previous.backends.iter().find(|old| old.socket_address == backend.socket_address);
+                if let Some(old) = old {
+                    backend.active = Arc::clone(&old.active);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher}
+};
+
+thread_local! {
+    // state of the generator of each thread, never 0
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+/// A random seed, different for every thread and every run
+fn seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(&hasher as *const _ as usize);
+    hasher.finish() | 1
+}
+
+/// Return a random number in 0..bound, bound must be greater than 0.
+/// Uses a thread local xorshift64* generator: fast and lock free,
+/// not suitable for cryptographic purposes.
+pub fn random_below(bound: u64) -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        // multiply-shift reduction, unbiased enough for balancing
+        ((x.wrapping_mul(0x2545F4914F6CDD1D) as u128 * bound as u128) >> 64) as u64
+    })
+}
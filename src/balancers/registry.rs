@@ -0,0 +1,85 @@
+use super::{
+    Algorithm,
+    LoadBalancer,
+    standard_weighted_load_balancer::load_balancer::WeightedRoundRobinLB,
+    least_connections_load_balancer::load_balancer::LeastConnectionsLB,
+    smooth_weighted_load_balancer::load_balancer::SmoothWeightedRoundRobinLB,
+    consistent_hash_load_balancer::load_balancer::ConsistentHashLB,
+    maglev_load_balancer::load_balancer::MaglevLB,
+    power_of_two_choices_load_balancer::load_balancer::PowerOfTwoChoicesLB,
+    peak_ewma_load_balancer::load_balancer::PeakEwmaLB
+};
+
+
+/// Create an empty load balancer, with the capacity for the given number of servers
+pub type Constructor = fn(usize) -> Result<Box<dyn LoadBalancer>, &'static str>;
+
+/// An algorithm that can be chosen with the "Algorithm" key
+pub struct Registration {
+    pub algorithm: Algorithm,
+    /// the name used in the json and on the command line
+    pub name: &'static str,
+    pub constructor: Constructor
+}
+
+// Every algorithm must have exactly one registration
+static REGISTRY: [Registration; 7] = [
+    Registration {
+        algorithm: Algorithm::WeightedRoundRobin,
+        name: "weighted_round_robin",
+        constructor: boxed::<WeightedRoundRobinLB>
+    },
+    Registration {
+        algorithm: Algorithm::LeastConnections,
+        name: "least_connections",
+        constructor: boxed::<LeastConnectionsLB>
+    },
+    Registration {
+        algorithm: Algorithm::SmoothWeightedRoundRobin,
+        name: "smooth_weighted_round_robin",
+        constructor: boxed::<SmoothWeightedRoundRobinLB>
+    },
+    Registration {
+        algorithm: Algorithm::ConsistentHash,
+        name: "consistent_hash",
+        constructor: boxed::<ConsistentHashLB>
+    },
+    Registration {
+        algorithm: Algorithm::Maglev,
+        name: "maglev",
+        constructor: boxed::<MaglevLB>
+    },
+    Registration {
+        algorithm: Algorithm::PowerOfTwoChoices,
+        name: "power_of_two_choices",
+        constructor: boxed::<PowerOfTwoChoicesLB>
+    },
+    Registration {
+        algorithm: Algorithm::PeakEwma,
+        name: "peak_ewma",
+        constructor: boxed::<PeakEwmaLB>
+    }
+];
+
+/// Return all the registered algorithms
+pub fn registrations() -> &'static [Registration] {
+    &REGISTRY
+}
+
+/// Return the registration of an algorithm
+pub fn registration(algorithm: Algorithm) -> &'static Registration {
+    REGISTRY.iter()
+        .find(|registration| registration.algorithm == algorithm)
+        .expect("every algorithm is registered")
+}
+
+/// Return the registration with the given name, if any
+pub fn find(name: &str) -> Option<&'static Registration> {
+    REGISTRY.iter().find(|registration| registration.name == name)
+}
+
+/// The constructor of a load balancer type, as a trait object
+fn boxed<T>(servers_number: usize) -> Result<Box<dyn LoadBalancer>, &'static str>
+where T: LoadBalancer + 'static {
+    T::new(servers_number).map(|balancer| balancer as Box<dyn LoadBalancer>)
+}
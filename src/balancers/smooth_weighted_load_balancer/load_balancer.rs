@@ -0,0 +1,99 @@
+use std::{any::Any, sync::Mutex};
+use crate::{
+    server::socket_address::*,
+    balancers::{
+        LoadBalancer,
+        RequestContext,
+        MAX_SERVERS,
+        TOO_MANY_SERVERS,
+        ZERO_OR_NEGATIVE_SERVERS
+    }
+};
+
+/// The main struct for storing the Socket Address of each server
+/// and perform Smooth Weighted Round Robin Algorithm (as in nginx).
+/// At every request each server's current weight grows by its weight,
+/// the server with the highest current weight is chosen and its
+/// current weight is decreased by the total weight. The selections are
+/// interleaved, e.g. weights 5,1,1 give a a b a c a a.
+#[derive(Debug)]
+pub struct SmoothWeightedRoundRobinLB {
+    /// stores the Socket Address and the weight of each server
+    addresses: Vec<(SocketAddress, usize)>,
+    /// current weight of each server, same order of addresses
+    current_weights: Mutex<Vec<i64>>,
+    /// true if every weight is 0, then every server has weight 1
+    all_zero: bool
+}
+
+impl SmoothWeightedRoundRobinLB {
+
+    /// Return the len of the inner vector
+    #[allow(dead_code)]
+    pub fn n_of_servers(&self) -> usize {
+        self.addresses.len()
+    }
+
+}
+
+impl LoadBalancer for SmoothWeightedRoundRobinLB {
+
+    fn new(servers_number: usize) -> Result<Box<Self>, &'static str> {
+        if servers_number > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        if servers_number == 0 {
+            return Err(ZERO_OR_NEGATIVE_SERVERS);
+        }
+        Ok(Box::new(SmoothWeightedRoundRobinLB {
+            addresses: Vec::with_capacity(servers_number),
+            current_weights: Mutex::new(Vec::with_capacity(servers_number)),
+            all_zero: true
+        }))
+    }
+
+    fn insert_socket_address(&mut self, socket_address: SocketAddress, weight: usize) -> Result<(), &'static str> {
+        if self.addresses.len() + 1 > MAX_SERVERS {
+            return Err(TOO_MANY_SERVERS);
+        }
+        self.addresses.push((socket_address, weight));
+        self.current_weights.get_mut().unwrap().push(0);
+        self.all_zero &= weight == 0;
+        Ok(())
+    }
+
+    fn next_server(&self, context: &RequestContext) -> Option<&SocketAddress> {
+        let mut current_weights = self.current_weights.lock().unwrap();
+        let mut total = 0;
+        let mut best: Option<usize> = None;
+        // the servers that aren't available are left out of the round
+        for (i, (socket_address, weight)) in self.addresses.iter().enumerate() {
+            // all the weights are 0, plain round robin
+            let weight = if self.all_zero { 1 } else { *weight as i64 };
+            if weight == 0 || !context.is_available(socket_address) {
+                continue;
+            }
+            current_weights[i] += weight;
+            total += weight;
+            if best.is_none_or(|b| current_weights[i] > current_weights[b]) {
+                best = Some(i);
+            }
+        }
+        let best = best?;
+        current_weights[best] -= total;
+        Some(&self.addresses[best].0)
+    }
+
+    fn inherit(&mut self, previous: &dyn LoadBalancer) {
+        if let Some(previous) = (previous as &dyn Any).downcast_ref::<Self>() {
+            let previous_weights = previous.current_weights.lock().unwrap();
+            let current_weights = self.current_weights.get_mut().unwrap();
+            for (i, (socket_address, _)) in self.addresses.iter().enumerate() {
+                let old = previous.addresses.iter().position(|(old, _)| old == socket_address);
+                if let Some(old) = old {
+                    current_weights[i] = previous_weights[old];
+                }
+            }
+        }
+    }
+}
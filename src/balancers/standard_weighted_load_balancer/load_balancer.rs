@@ -1,42 +1,49 @@
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use crate::{
     server::socket_address::*,
     balancers::{
         standard_weighted_load_balancer::weight::*, 
-        LoadBalancer
+        LoadBalancer,
+        RequestContext
     }
 };
-
-// Max number of servers
-pub const MAX_SERVERS: usize = 256;
-
-// Error messages
-pub static TOO_MANY_SERVERS: &str = "The number of servers is over 256";
-pub static ZERO_OR_NEGATIVE_SERVERS: &str = "The number of servers is 0 or a negative number";
-pub static INDEX_PROBLEM: &str = "There is a problem with the index";
+pub use crate::balancers::{MAX_SERVERS, TOO_MANY_SERVERS, ZERO_OR_NEGATIVE_SERVERS};
 
 /// The main struct for storing the Socket Address of each server
-/// and perform Round Robin Algorithm
+/// and perform Round Robin Algorithm.
+/// The selection is wait-free: a single global counter is mapped onto
+/// the weighted schedule, where each server occupies "weight" consecutive
+/// slots, by a binary search on the cumulative weights.
 #[derive(Debug)]
 pub struct WeightedRoundRobinLB {
     /// stores the Scoket Address of each server
     addresses: Vec<Weight>,
-    /// index of the vector, used for concurrent access at the vector
-    index: Arc<Mutex<usize>>
+    /// cumulative weights, cumulative_weights[i] is the end (excluded)
+    /// of the slots of addresses[i] in the schedule
+    cumulative_weights: Vec<usize>,
+    /// number of requests served, used for concurrent access at the vector
+    counter: AtomicUsize
 }
 
 impl WeightedRoundRobinLB {
 
     /// Return the len of the inner vector
+    #[allow(dead_code)]
     pub fn n_of_servers(&self) -> usize {
         self.addresses.len()
     }
 
     // Return the capacity of the inner vector
+    #[allow(dead_code)]
     pub fn capacity(&self) -> usize {
         self.addresses.capacity()
     }
 
+    /// Return the total weight, that is the len of the schedule
+    fn total_weight(&self) -> usize {
+        self.cumulative_weights.last().copied().unwrap_or(0)
+    }
+
 }
 
 impl LoadBalancer for WeightedRoundRobinLB {
@@ -45,12 +52,13 @@ impl LoadBalancer for WeightedRoundRobinLB {
         if servers_number > MAX_SERVERS {
             return Err(TOO_MANY_SERVERS);
         }
-        if servers_number <= 0 {
+        if servers_number == 0 {
             return Err(ZERO_OR_NEGATIVE_SERVERS);
         }
         Ok(Box::new(WeightedRoundRobinLB { 
             addresses: Vec::with_capacity(servers_number),
-            index: Arc::new(Mutex::new(0))
+            cumulative_weights: Vec::with_capacity(servers_number),
+            counter: AtomicUsize::new(0)
         }))
     }
 
@@ -58,20 +66,32 @@ impl LoadBalancer for WeightedRoundRobinLB {
         if self.addresses.len() + 1 > MAX_SERVERS {
             return Err(TOO_MANY_SERVERS);
         }
-        self.addresses.push(Weight::new(socket_address, weight));
+        let weight = Weight::new(socket_address, weight);
+        self.cumulative_weights.push(self.total_weight() + weight.weight());
+        self.addresses.push(weight);
         Ok(())
     }
 
-    fn next_server(&self) -> &SocketAddress {
-        let mut idx = self.index.lock().unwrap();
-        let option = self.addresses[*idx].next_request();
-        
-        match option {
-            Some(socket_address) => return socket_address,
-            None => {
-                *idx = (*idx + 1) % self.addresses.len();
-                return self.addresses[*idx].next_request().expect(INDEX_PROBLEM);
-            }
-        }
+    fn next_server(&self, context: &RequestContext) -> Option<&SocketAddress> {
+        let n = self.counter.fetch_add(1, Ordering::Relaxed);
+        let total_weight = self.total_weight();
+        let len = self.addresses.len();
+
+        // all the weights are 0, plain round robin
+        let idx = if total_weight == 0 {
+            n % len
+        } else {
+            let slot = n % total_weight;
+            self.cumulative_weights.partition_point(|end| *end <= slot)
+        };
+
+        // if the server isn't available, the next available one in the array
+        (idx..len).chain(0..idx)
+            .map(|i| &self.addresses[i])
+            .find(|weight| {
+                (weight.weight() > 0 || total_weight == 0)
+                    && context.is_available(weight.socket_address())
+            })
+            .map(|weight| weight.socket_address())
     }
-}
\ No newline at end of file
+}
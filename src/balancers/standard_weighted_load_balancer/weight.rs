@@ -1,30 +1,27 @@
-use std::sync::Mutex;
-
 use crate::server::socket_address::SocketAddress;
 
+/// A server with its weight
 #[derive(Debug)]
 pub struct Weight {
     socket_address: SocketAddress,
-    weight: usize,
-    request_counter: Mutex<usize>
+    weight: usize
 }
 
 impl Weight {
     pub fn new(socket_address: SocketAddress, weight: usize) -> Self {
         Weight {
             socket_address,
-            weight,
-            request_counter: Mutex::new(0)
+            weight
         }
     }
 
-    pub fn next_request(&self) -> Option<&SocketAddress> {
-        let mut counter = self.request_counter.lock().unwrap();
-        if *counter == self.weight {
-            *counter = 0;
-            return None;
-        }
-        *counter += 1;
-        Some(&self.socket_address)
+    /// Return the socket address of the server
+    pub fn socket_address(&self) -> &SocketAddress {
+        &self.socket_address
     }
-}
\ No newline at end of file
+
+    /// Return the weight of the server
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+}
@@ -0,0 +1,147 @@
+use std::{collections::HashMap, fmt, net::IpAddr};
+use super::Config;
+use crate::server::{
+    socket_address::SocketAddress,
+    health_check::{check_http, check_tcp, HttpCheck, DEFAULT_TIMEOUT}
+};
+
+// warning messages
+static ZERO_WEIGHT: &str = "The weight is 0, the server may never be chosen";
+static UNREACHABLE_SERVER: &str = "The server isn't reachable";
+static UNHEALTHY_SERVER: &str = "The server doesn't pass its HTTP check";
+static DUAL_STACK_ON_IPV4: &str = "\"dual_stack\" has effect only on an IPv6 address";
+static ADMIN_NOT_LOOPBACK: &str = "The admin API has no authentication, it should listen on a loopback address";
+
+
+/// A field of the configuration that is valid but probably wrong,
+/// with the json path of the field, e.g. `Servers[2].weight`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigWarning {
+    pub path: String,
+    pub message: String
+}
+
+impl ConfigWarning {
+    fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        ConfigWarning { path: path.into(), message: message.into() }
+    }
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+
+/// Return the warnings of a valid configuration:
+/// duplicate servers, zero weights, a listener that collides
+/// with a server, so the balancer would connect to itself, and
+/// an admin API reachable from the network.
+/// The servers with a hostname aren't resolved, only their weight is checked.
+/// # Arguments
+///
+/// * `config` - the configuration returned by `configure`
+pub fn config_warnings(config: &Config) -> Vec<ConfigWarning> {
+    let mut warnings = Vec::new();
+    let mut first_path: HashMap<&SocketAddress, String> = HashMap::new();
+
+    for ((socket_address, weight), path) in config.servers.iter().zip(server_paths(config)) {
+        match first_path.get(socket_address) {
+            Some(first) => warnings.push(ConfigWarning::new(
+                &path, format!("{} is a duplicate of {}", socket_address, first)
+            )),
+            None => {
+                first_path.insert(socket_address, path.clone());
+            }
+        }
+        if *weight == 0 {
+            warnings.push(ConfigWarning::new(format!("{path}.weight"), ZERO_WEIGHT));
+        }
+        if collides(&config.listen_to, config.dual_stack, socket_address) {
+            warnings.push(ConfigWarning::new(
+                "Listen_to", format!("The balancer listens on {}, the address of {}", config.listen_to, path)
+            ));
+        }
+    }
+    for host in &config.hosts {
+        if host.weight == 0 {
+            warnings.push(ConfigWarning::new(format!("{}.weight", host.path()), ZERO_WEIGHT));
+        }
+    }
+    if !config.dual_stack && !config.listen_to.is_ipv6() {
+        warnings.push(ConfigWarning::new("Listen_to.dual_stack", DUAL_STACK_ON_IPV4));
+    }
+    if config.admin.as_ref().is_some_and(|admin| !admin.ip().is_loopback()) {
+        warnings.push(ConfigWarning::new("Admin", ADMIN_NOT_LOOPBACK));
+    }
+    warnings
+}
+
+/// Return the json paths of the servers with an ip address,
+/// the positions of the servers with a hostname are skipped
+fn server_paths(config: &Config) -> impl Iterator<Item = String> + '_ {
+    config.server_indexes().map(|i| format!("Servers[{i}]"))
+}
+
+/// Return true if the connections to the server would be accepted by the listener
+fn collides(listen_to: &SocketAddress, dual_stack: bool, server: &SocketAddress) -> bool {
+    if listen_to.port() != server.port() {
+        return false;
+    }
+    if listen_to == server {
+        return true;
+    }
+    // a listener on the unspecified address receives the connections to the local addresses
+    let (listen_ip, server_ip) = (listen_to.ip(), server.ip());
+    let same_family = listen_ip.is_ipv6() == server_ip.is_ipv6() || (listen_ip.is_ipv6() && dual_stack);
+    listen_ip.is_unspecified() && same_family && (server_ip.is_loopback() || server_ip.is_unspecified())
+}
+
+
+/// Try to reach every server, with its HTTP check if configured,
+/// otherwise with a TCP connect. The servers are probed concurrently.
+/// # Arguments
+///
+/// * `config` - the configuration returned by `configure`
+/// * `resolved` - the addresses of the hosts of the configuration, in the same order
+///
+/// # Return
+///
+/// * A warning for every server (or address of a host) that can't be reached
+pub async fn probe_servers(config: &Config, resolved: &[Vec<IpAddr>]) -> Vec<ConfigWarning> {
+    let timeout = config.health_check.as_ref().map_or(DEFAULT_TIMEOUT, |health_check| health_check.timeout);
+    let http_check = |socket_address: &SocketAddress| config.health_check.as_ref()
+        .and_then(|health_check| health_check.http_checks.get(socket_address).cloned());
+    let mut targets: Vec<(String, SocketAddress, Option<HttpCheck>)> = config.servers.iter()
+        .zip(server_paths(config))
+        .map(|((socket_address, _), path)| (path, socket_address.clone(), http_check(socket_address)))
+        .collect();
+    for (host, addresses) in config.hosts.iter().zip(resolved) {
+        for ip in addresses {
+            targets.push((host.path(), host.socket_address(ip), host.http_check.clone()));
+        }
+    }
+
+    let probes: Vec<_> = targets.iter().map(|(_, socket_address, http_check)| {
+        let (socket_address, http_check) = (socket_address.clone(), http_check.clone());
+        tokio::spawn(async move {
+            match http_check {
+                Some(http_check) => check_http(&socket_address, &http_check, timeout).await
+                    .then_some(())
+                    .ok_or(UNHEALTHY_SERVER),
+                None => check_tcp(&socket_address, timeout).await
+                    .then_some(())
+                    .ok_or(UNREACHABLE_SERVER)
+            }
+        })
+    }).collect();
+
+    let mut warnings = Vec::new();
+    for ((path, socket_address, _), probe) in targets.iter().zip(probes) {
+        if let Ok(Err(message)) = probe.await {
+            warnings.push(ConfigWarning::new(path, format!("{} ({})", message, socket_address)));
+        }
+    }
+    warnings
+}
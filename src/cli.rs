@@ -0,0 +1,178 @@
+use std::path::PathBuf;
+use crate::{
+    balancers::{Algorithm, Config},
+    log::LogLevel,
+    server::socket_address::SocketAddress
+};
+
+// Default path of the configuration file, relative to the working directory
+pub static DEFAULT_CONFIG_PATH: &str = "config.json";
+
+// environment variables, used when the option isn't on the command line
+pub static CONFIG_ENV: &str = "LOAD_BALANCER_CONFIG";
+pub static LISTEN_ENV: &str = "LOAD_BALANCER_LISTEN";
+pub static ALGORITHM_ENV: &str = "LOAD_BALANCER_ALGORITHM";
+pub static LOG_LEVEL_ENV: &str = "LOAD_BALANCER_LOG_LEVEL";
+
+// error messages
+static MISSING_VALUE: &str = "A value is required";
+static INVALID_LISTEN: &str = "Must be an address with format IPv4:PORT or [IPv6]:PORT";
+static INVALID_ALGORITHM: &str = "Isn't a known algorithm";
+static UNKNOWN_ARGUMENT: &str = "Unknown argument";
+
+pub static USAGE: &str = "\
+Usage: load_balancer_rust [OPTIONS]
+
+Options:
+  --config <path>          configuration file [env: LOAD_BALANCER_CONFIG] [default: config.json]
+  --listen <ip:port>       overrides \"Listen_to\" [env: LOAD_BALANCER_LISTEN]
+  --algorithm <name>       overrides \"Algorithm\" [env: LOAD_BALANCER_ALGORITHM]
+  --log-level <level>      error, warn, info or debug [env: LOAD_BALANCER_LOG_LEVEL] [default: info]
+  --check-config [path]    validate the configuration and exit
+  --probe                  with --check-config, also try to reach the servers
+  --version                print the version and exit
+  --help                   print this help and exit";
+
+
+/// What the binary has to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// run the load balancer
+    Run,
+    /// validate the configuration and exit, `probe` tries to reach the servers
+    CheckConfig { probe: bool },
+    /// print the version and exit
+    Version,
+    /// print the usage and exit
+    Help
+}
+
+
+/// Values that replace the ones of the configuration file,
+/// applied at startup and at every reload
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides {
+    pub listen: Option<SocketAddress>,
+    pub algorithm: Option<Algorithm>
+}
+
+impl Overrides {
+    /// Replace the values of the configuration with the overridden ones
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(listen) = &self.listen {
+            config.listen_to = listen.clone();
+        }
+        if let Some(algorithm) = self.algorithm {
+            config.algorithm = algorithm;
+        }
+    }
+}
+
+
+/// An invalid command line argument or environment variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliError {
+    /// the argument or the environment variable
+    pub argument: String,
+    pub reason: &'static str
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.argument, self.reason)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+
+/// The parsed command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cli {
+    pub command: Command,
+    pub config: PathBuf,
+    pub overrides: Overrides,
+    pub log_level: LogLevel
+}
+
+impl Cli {
+    /// Parse the command line arguments, falling back on the environment
+    /// variables for the options that aren't given.
+    /// Every option can be written as `--option value` or `--option=value`.
+    /// # Arguments
+    ///
+    /// * `args` - the arguments, without the name of the binary
+    /// * `env` - return the value of an environment variable, if set
+    ///
+    /// # Return
+    ///
+    /// * The parsed command line, or the first invalid argument
+    pub fn parse<I, E>(args: I, env: E) -> Result<Self, CliError>
+    where I: IntoIterator<Item = String>, E: Fn(&str) -> Option<String> {
+        let mut command = Command::Run;
+        let mut probe = false;
+        let (mut config, mut listen, mut algorithm, mut log_level) = (None, None, None, None);
+
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name.to_string(), Some(value.to_string())),
+                _ => (arg, None)
+            };
+            let mut value = || inline_value.clone()
+                .or_else(|| args.next_if(|next| !next.starts_with("--")))
+                .ok_or_else(|| CliError { argument: name.clone(), reason: MISSING_VALUE });
+            match name.as_str() {
+                "--config" => config = Some(value()?),
+                "--listen" => listen = Some((name.clone(), value()?)),
+                "--algorithm" => algorithm = Some((name.clone(), value()?)),
+                "--log-level" => log_level = Some((name.clone(), value()?)),
+                "--check-config" => {
+                    command = Command::CheckConfig { probe: false };
+                    // the path is optional, the default is the one of --config
+                    if let Ok(path) = value() {
+                        config = Some(path);
+                    }
+                },
+                "--probe" => probe = true,
+                "--version" | "-V" => return Ok(Self::only(Command::Version)),
+                "--help" | "-h" => return Ok(Self::only(Command::Help)),
+                _ => return Err(CliError { argument: name, reason: UNKNOWN_ARGUMENT })
+            }
+        }
+        if let Command::CheckConfig { .. } = command {
+            command = Command::CheckConfig { probe };
+        }
+
+        // the command line has priority over the environment
+        let from_env = |variable: &'static str| env(variable).map(|value| (variable.to_string(), value));
+        let config = config.or_else(|| env(CONFIG_ENV)).unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+        let listen = listen.or_else(|| from_env(LISTEN_ENV))
+            .map(|(argument, value)| value.parse().map_err(|_| CliError { argument, reason: INVALID_LISTEN }))
+            .transpose()?;
+        let algorithm = algorithm.or_else(|| from_env(ALGORITHM_ENV))
+            .map(|(argument, value)| Algorithm::from_name(&value).ok_or(CliError { argument, reason: INVALID_ALGORITHM }))
+            .transpose()?;
+        let log_level = log_level.or_else(|| from_env(LOG_LEVEL_ENV))
+            .map(|(argument, value)| value.parse().map_err(|reason| CliError { argument, reason }))
+            .transpose()?
+            .unwrap_or(LogLevel::Info);
+
+        Ok(Cli {
+            command,
+            config: PathBuf::from(config),
+            overrides: Overrides { listen, algorithm },
+            log_level
+        })
+    }
+
+    /// Return a command line with only the command and the defaults
+    fn only(command: Command) -> Self {
+        Cli {
+            command,
+            config: PathBuf::from(DEFAULT_CONFIG_PATH),
+            overrides: Overrides::default(),
+            log_level: LogLevel::Info
+        }
+    }
+}
@@ -1,38 +1,230 @@
+#[macro_use]
+mod log;
+mod cli;
 mod server;
 mod balancers;
 mod tests;
 
-use std::path::Path;
+use std::{path::{Path, PathBuf}, process};
 use balancers::{
-    standard_weighted_load_balancer::load_balancer::WeightedRoundRobinLB, 
-    configure
-};
-use tokio::signal;
-use server::{
-    app::Server
+    configure,
+    create_balancer,
+    registry,
+    config_error::ConfigError,
+    validation::{config_warnings, probe_servers},
+    Config
 };
+use cli::{Cli, Command, Overrides, USAGE};
+use tokio::{signal, sync::mpsc};
+use server::{app::Server, dns};
+
+// exit codes
+const EXIT_INVALID_CONFIG: i32 = 1;
+const EXIT_CONFIG_WARNINGS: i32 = 2;
+const EXIT_USAGE: i32 = 64;
 
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    println!("Configuring the server...");
-    let (server_soc, servers_vec) = configure(Path::new("config.json"));
-    println!("Configuration completed...");
-
-    tokio::spawn(async move {
-        Server::new(server_soc)
-            .run::<WeightedRoundRobinLB>(servers_vec)
-            .await;
-    });
-
-    match signal::ctrl_c().await {
-        Ok(()) => println!("\nShutting down the server..."),
-        Err(err) => {
-            eprintln!("Unable to listen for shutdown signal: {}", err);
-            // we also shut down in case of error
-            println!("\nShutting down the server...")
+    let cli = match Cli::parse(std::env::args().skip(1), |variable| std::env::var(variable).ok()) {
+        Ok(cli) => cli,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            process::exit(EXIT_USAGE)
+        }
+    };
+    log::set_level(cli.log_level);
+
+    match cli.command {
+        Command::Run => (),
+        Command::CheckConfig { probe } => process::exit(check_config(&cli.config, &cli.overrides, probe).await),
+        Command::Version => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            return Ok(())
         },
+        Command::Help => {
+            let algorithms: Vec<_> = registry::registrations().iter()
+                .map(|registration| registration.name)
+                .collect();
+            println!("{}\n\nAlgorithms: {}", USAGE, algorithms.join(", "));
+            return Ok(())
+        }
+    }
+
+    log_info!("Configuring the server...");
+    let mut config = match configure(&cli.config) {
+        Ok(config) => config,
+        Err(errors) => exit_with_errors(&errors)
+    };
+    cli.overrides.apply(&mut config);
+    log_info!("Configuration completed...");
+
+    let (reload_sender, reloads) = mpsc::channel(1);
+    tokio::spawn(reload_on_sighup(cli.config, cli.overrides, reload_sender));
+
+    let mut server = Server::new(config);
+    if let Err(errors) = server.run(shutdown_signal(), reloads).await {
+        exit_with_errors(&errors);
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+
+/// Validate a configuration file without starting the server
+/// and print every error and warning found.
+/// The hostnames of the servers are resolved.
+/// # Arguments
+///
+/// * `config_path` - the path of the configuration json file
+/// * `overrides` - the values of the command line that replace the ones of the file
+/// * `probe` - if true, every server is also contacted
+///
+/// # Return
+///
+/// * The exit code: 0 if the configuration is valid, 1 if it has
+///   errors, 2 if it's valid but has warnings
+async fn check_config(config_path: &Path, overrides: &Overrides, probe: bool) -> i32 {
+    let config = match configure(config_path) {
+        Ok(mut config) => {
+            overrides.apply(&mut config);
+            config
+        },
+        Err(errors) => {
+            for error in &errors {
+                println!("error: {}", error);
+            }
+            println!("{}: {}", config_path.display(), plural(errors.len(), "error"));
+            return EXIT_INVALID_CONFIG;
+        }
+    };
+
+    let resolved: Vec<_> = match dns::resolve_hosts(&config.hosts, &dns::SystemResolver).await {
+        Ok(resolutions) => resolutions.into_iter().map(|resolution| resolution.addresses).collect(),
+        Err(errors) => {
+            for error in &errors {
+                println!("error: {}", error);
+            }
+            println!("{}: {}", config_path.display(), plural(errors.len(), "error"));
+            return EXIT_INVALID_CONFIG;
+        }
+    };
+
+    let mut warnings = config_warnings(&config);
+    if probe {
+        warnings.extend(probe_servers(&config, &resolved).await);
+    }
+
+    let (host_servers, _) = dns::expand(&config.hosts, &resolved);
+    let servers = config.server_indexes()
+        .zip(config.servers.iter().cloned())
+        .chain(host_servers)
+        .collect();
+    let result = create_balancer(config.algorithm, servers, &config.algorithm_options);
+    for warning in &warnings {
+        println!("warning: {}", warning);
+    }
+    let errors = match result {
+        Ok(_) => 0,
+        Err(error) => {
+            println!("error: {}", error);
+            1
+        }
+    };
+    println!("{}: {}, {}", config_path.display(), plural(errors, "error"), plural(warnings.len(), "warning"));
+    if errors > 0 {
+        EXIT_INVALID_CONFIG
+    } else if warnings.is_empty() {
+        0
+    } else {
+        EXIT_CONFIG_WARNINGS
+    }
+}
+
+
+/// Formats a count followed by the word, in the plural unless the count is 1
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, word)
+    } else {
+        format!("{} {}s", count, word)
+    }
+}
+
+
+/// Print all the configuration errors and exit with a non zero code
+fn exit_with_errors(errors: &[ConfigError]) -> ! {
+    eprintln!("Invalid configuration, {}:", plural(errors.len(), "error"));
+    for error in errors {
+        eprintln!("  {}", error);
+    }
+    process::exit(EXIT_INVALID_CONFIG)
+}
+
+
+/// Completes on CTRL+C or, on unix, on SIGTERM (e.g. from systemd or a container runtime)
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            },
+            Err(err) => {
+                log_error!("Unable to listen for SIGTERM: {}", err);
+                std::future::pending::<()>().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        result = signal::ctrl_c() => if let Err(err) = result {
+            log_error!("Unable to listen for shutdown signal: {}", err);
+            // we also shut down in case of error
+        },
+        _ = terminate => ()
+    }
+    log_info!("\nShutting down the server...");
+}
+
+
+/// Reads the configuration again on every SIGHUP and sends it to the server.
+/// An invalid configuration is logged and the server keeps the old one.
+/// # Arguments
+///
+/// * `config_path` - the path of the configuration json file
+/// * `overrides` - the values of the command line that replace the ones of the file
+/// * `reloads` - the channel on which to send the new configurations
+#[cfg(unix)]
+async fn reload_on_sighup(config_path: PathBuf, overrides: Overrides, reloads: mpsc::Sender<Config>) {
+    let mut hangup = match signal::unix::signal(signal::unix::SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            log_error!("Unable to listen for SIGHUP, the reload is disabled: {}", err);
+            return
+        }
+    };
+    while hangup.recv().await.is_some() {
+        log_info!("Reloading the configuration...");
+        match configure(&config_path) {
+            Ok(mut config) => {
+                overrides.apply(&mut config);
+                if reloads.send(config).await.is_err() {
+                    return
+                }
+            },
+            Err(errors) => {
+                log_error!("reload: invalid configuration, the old one is kept:"); // log
+                for error in errors {
+                    log_error!("  {}", error); // log
+                }
+            }
+        }
+    }
+}
+
+/// The reload on SIGHUP is available only on unix
+#[cfg(not(unix))]
+async fn reload_on_sighup(_config_path: PathBuf, _overrides: Overrides, _reloads: mpsc::Sender<Config>) {}
@@ -0,0 +1,226 @@
+use std::{io, time::Duration};
+use serde_json::{json, Map, Value};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+    time
+};
+use super::{http, socket_address::SocketAddress};
+
+// Max size of the body of a request, the rest is ignored
+pub const MAX_BODY_SIZE: u64 = 64 * 1024;
+// Max time for reading a request
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// error messages
+pub static UNKNOWN_ENDPOINT: &str = "Unknown endpoint";
+pub static METHOD_NOT_ALLOWED: &str = "The method isn't allowed on this endpoint";
+pub static INVALID_BODY: &str = "The body must be a json object";
+pub static MISSING_ADDRESS: &str = "\"address\" is required, e.g. \"10.0.0.1:8080\"";
+pub static INVALID_WEIGHT: &str = "\"weight\" must be a non negative integer";
+pub static INVALID_PATH: &str = "The path isn't valid percent-encoded UTF-8";
+pub static UNKNOWN_SERVER: &str = "The server isn't in the pool";
+pub static DUPLICATE_SERVER: &str = "The server is already in the pool";
+pub static HOST_SERVER: &str = "The server is an address of a hostname, change it in the configuration";
+pub static SERVER_STOPPED: &str = "The load balancer is shutting down";
+
+
+/// A change (or a read) of the servers asked to the admin API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminRequest {
+    /// `GET /backends`
+    List,
+    /// `POST /backends` with `{"address": "10.0.0.1:8080", "weight": 1}`
+    Add { socket_address: SocketAddress, weight: usize },
+    /// `DELETE /backends/{address}`
+    Remove(SocketAddress),
+    /// `PUT /backends/{address}/weight` with `{"weight": 3}`
+    Reweight { socket_address: SocketAddress, weight: usize },
+    /// `POST /backends/{address}/drain`
+    Drain(SocketAddress),
+    /// `DELETE /backends/{address}/drain`
+    Undrain(SocketAddress),
+    /// `GET /stats`
+    Stats
+}
+
+/// The status code and the json body of a response
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdminResponse {
+    pub status: u16,
+    pub body: Value
+}
+
+impl AdminResponse {
+    /// Return a 200 response with the given body
+    pub fn ok(body: Value) -> Self {
+        AdminResponse { status: 200, body }
+    }
+
+    /// Return an error response, the body is `{"error": message}`
+    pub fn error(status: u16, message: &str) -> Self {
+        AdminResponse { status, body: json!({ "error": message }) }
+    }
+}
+
+/// A request sent to the server, that applies it between two
+/// accepted connections and sends back the response
+#[derive(Debug)]
+pub struct AdminCommand {
+    pub request: AdminRequest,
+    pub reply: oneshot::Sender<AdminResponse>
+}
+
+
+/// Accept the connections of the admin API, one request for each
+/// connection, and send the requests to the server.
+/// # Arguments
+///
+/// * `listener` - the listener of the admin API
+/// * `commands` - the channel on which to send the requests
+pub async fn serve(listener: TcpListener, commands: mpsc::Sender<AdminCommand>) {
+    loop {
+        match listener.accept().await {
+            Ok((socket, client_address)) => {
+                let commands = commands.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = handle(socket, commands).await {
+                        log_warn!("admin: {} - {}", client_address, error); // log
+                    }
+                });
+            },
+            Err(error) => log_error!("admin: accept: {error}")
+        }
+    }
+}
+
+/// Read a request, wait for the server to apply it and write the response
+async fn handle(mut socket: TcpStream, commands: mpsc::Sender<AdminCommand>) -> io::Result<()> {
+    let (reader, mut writer) = socket.split();
+    let mut reader = BufReader::new(reader);
+    let read = async {
+        let Some(head) = http::read_head(&mut reader).await? else {
+            return Ok(None)
+        };
+        let length = head.body_length(true)?;
+        let body = http::read_body(&mut reader, length, MAX_BODY_SIZE).await?;
+        Ok::<_, io::Error>(Some((head, body)))
+    };
+    let (head, body) = match time::timeout(REQUEST_TIMEOUT, read).await {
+        Ok(Ok(Some(request))) => request,
+        // closed without a request
+        Ok(Ok(None)) => return Ok(()),
+        Ok(Err(error)) if error.kind() == io::ErrorKind::InvalidData => {
+            return write_response(&mut writer, &AdminResponse::error(400, &error.to_string())).await
+        },
+        Ok(Err(error)) => return Err(error),
+        Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out"))
+    };
+
+    let response = match parse_request(head.method(), &head.start_line.1, &body) {
+        Ok(request) => {
+            let (reply, response) = oneshot::channel();
+            match commands.send(AdminCommand { request, reply }).await {
+                Ok(()) => response.await.unwrap_or_else(|_| AdminResponse::error(503, SERVER_STOPPED)),
+                Err(_) => AdminResponse::error(503, SERVER_STOPPED)
+            }
+        },
+        Err(response) => response
+    };
+    log_info!("admin: {} {} - {}", head.method(), head.start_line.1, response.status); // log
+    write_response(&mut writer, &response).await
+}
+
+/// Write a json response and close the connection
+async fn write_response<W>(writer: &mut W, response: &AdminResponse) -> io::Result<()>
+where W: AsyncWrite + Unpin {
+    let mut body = response.body.to_string();
+    body.push('\n');
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status, http::reason_phrase(response.status), body.len()
+    );
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(body.as_bytes()).await?;
+    writer.shutdown().await
+}
+
+
+/// Return the request of a method and a path of the admin API.
+/// The address in the path can be percent-encoded, e.g. `%5B::1%5D:8080`.
+/// # Arguments
+///
+/// * `method` - the HTTP method
+/// * `target` - the request target, the query is ignored
+/// * `body` - the body of the request, a json object for the ones with a body
+///
+/// # Return
+///
+/// * The request, or the error response to send back
+pub fn parse_request(method: &str, target: &str, body: &[u8]) -> Result<AdminRequest, AdminResponse> {
+    let path = target.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let address = |segment: &str| {
+        let segment = percent_decode(segment).ok_or_else(|| AdminResponse::error(400, INVALID_PATH))?;
+        segment.parse::<SocketAddress>().map_err(|error| AdminResponse::error(400, error.reason()))
+    };
+
+    match (segments.as_slice(), method) {
+        (["backends"], "GET") => Ok(AdminRequest::List),
+        (["backends"], "POST") => {
+            let body = parse_body(body)?;
+            let socket_address = match body.get("address") {
+                Some(Value::String(socket_address)) => address(socket_address)?,
+                _ => return Err(AdminResponse::error(400, MISSING_ADDRESS))
+            };
+            Ok(AdminRequest::Add { socket_address, weight: weight(&body)? })
+        },
+        (["backends", socket_address], "DELETE") => Ok(AdminRequest::Remove(address(socket_address)?)),
+        (["backends", socket_address, "weight"], "PUT") => Ok(AdminRequest::Reweight {
+            socket_address: address(socket_address)?,
+            weight: weight(&parse_body(body)?)?
+        }),
+        (["backends", socket_address, "drain"], "POST") => Ok(AdminRequest::Drain(address(socket_address)?)),
+        (["backends", socket_address, "drain"], "DELETE") => Ok(AdminRequest::Undrain(address(socket_address)?)),
+        (["stats"], "GET") => Ok(AdminRequest::Stats),
+        (["backends"] | ["backends", _] | ["backends", _, "weight" | "drain"] | ["stats"], _) => {
+            Err(AdminResponse::error(405, METHOD_NOT_ALLOWED))
+        },
+        _ => Err(AdminResponse::error(404, UNKNOWN_ENDPOINT))
+    }
+}
+
+/// Parse the body of a request, that must be a json object
+fn parse_body(body: &[u8]) -> Result<Map<String, Value>, AdminResponse> {
+    match serde_json::from_slice(body) {
+        Ok(Value::Object(body)) => Ok(body),
+        _ => Err(AdminResponse::error(400, INVALID_BODY))
+    }
+}
+
+/// Return the "weight" key of the body of a request
+fn weight(body: &Map<String, Value>) -> Result<usize, AdminResponse> {
+    body.get("weight")
+        .and_then(Value::as_u64)
+        .map(|weight| weight as usize)
+        .ok_or_else(|| AdminResponse::error(400, INVALID_WEIGHT))
+}
+
+/// Decode the percent-encoded bytes of a path segment
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            decoded.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
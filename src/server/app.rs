@@ -1,150 +1,1021 @@
 use core::panic;
-use std::sync::Arc;
+use std::{future::Future, io, net::IpAddr, sync::Arc, time::Instant};
+use socket2::{Domain, Protocol, Socket, Type};
+use serde_json::{json, Map, Value};
 use tokio::{
     net::{TcpListener, TcpStream},
-    io::{AsyncReadExt, AsyncWriteExt as _}
+    sync::mpsc,
+    task::JoinHandle,
+    io::{copy_bidirectional, AsyncBufRead, AsyncWrite, AsyncWriteExt, BufReader}
+};
+use super::{
+    socket_address::*,
+    admin::{self, AdminRequest, AdminResponse},
+    http,
+    backend_state::BackendStates,
+    health_check::{run_health_checks, HealthCheckConfig},
+    dns::{self, HostRefresher, HostUpdate, Resolution, Resolver, SystemResolver},
+    shutdown::Connections,
+    timeouts::{with_timeout, Side, TimedStream, TimeoutConfig, TimeoutKind}
 };
-use super::socket_address::*;
 use crate::balancers::{
+    Config,
+    config_error::ConfigError,
     LoadBalancer,
-    create_and_fill_the_balancer
+    RequestContext,
+    create_balancer
 };
 
-const INITIAL_BUFFER_SIZE: usize = 8193;
+/// A new configuration with the resolutions of its hosts,
+/// or the errors of the hosts that can't be resolved
+type ResolvedConfig = (Config, Result<Vec<Resolution>, Vec<ConfigError>>);
+
+/// How the accepted connections are proxied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyMode {
+    /// opaque bytes pumped in both directions (L4)
+    Tcp,
+    /// HTTP/1.1 requests, each one balanced separately (L7)
+    Http
+}
+
+/// A server chosen by the balancer for a connection (or request).
+/// It's the feedback path from the proxy to the balancer: reports the
+/// measured latencies and notifies the balancer when the connection
+/// is finished, whatever is the path that ends it. The results of the
+/// connections are reported to the outlier detection.
+struct ServerGuard<'a> {
+    balancer: &'a dyn LoadBalancer,
+    socket_address: &'a SocketAddress,
+    states: Option<Arc<BackendStates>>,
+    /// when the server was chosen, just before the connect
+    start: Instant
+}
+
+impl<'a> ServerGuard<'a> {
+    fn new(balancer: &'a dyn LoadBalancer, socket_address: &'a SocketAddress, context: &RequestContext) -> Self {
+        ServerGuard {
+            balancer,
+            socket_address,
+            states: context.states().cloned(),
+            start: Instant::now()
+        }
+    }
+
+    /// Report to the balancer the time passed since the server was chosen
+    fn report_latency(&self) {
+        self.balancer.report_latency(self.socket_address, self.start.elapsed());
+    }
+
+    /// Report the result of the connection (or request) to the outlier detection
+    fn report_result(&self, success: bool) {
+        if let Some(states) = &self.states {
+            if success {
+                states.report_success(self.socket_address);
+            } else {
+                states.report_failure(self.socket_address);
+            }
+        }
+    }
+}
+
+impl Drop for ServerGuard<'_> {
+    fn drop(&mut self) {
+        self.balancer.release(self.socket_address);
+    }
+}
+
+/// What happened to the client connection after a forwarded request
+enum HttpOutcome {
+    /// the client connection can be used for the next request
+    KeepAlive,
+    /// the client connection must be closed
+    Close,
+    /// the connection was upgraded and tunneled until the end
+    Upgraded,
+    /// the server closed the connection before any response byte,
+    /// nothing was sent to the client and the request can be retried
+    Reset
+}
+
+/// Configuration of the retries on another server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// max number of retries of a connection (or request), 0 disables them
+    pub max_retries: usize,
+    /// in HTTP mode, retry the idempotent requests without body
+    /// if the server closes the connection before any response byte
+    pub retry_on_reset: bool
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: 0,
+            retry_on_reset: true
+        }
+    }
+}
+
+/// The options of the proxy shared by all the connections
+struct ProxyOptions {
+    retries: RetryConfig,
+    timeouts: TimeoutConfig,
+    /// custom bodies of the error responses, HTTP mode only
+    error_pages: http::ErrorPages
+}
+
+/// Why a connection to a server couldn't be opened
+enum ConnectError {
+    /// the balancer has no available server
+    NoServer,
+    /// the connect to the last tried server failed
+    Failed(io::Error)
+}
+
+
+/// Everything the new connections need, built from the configuration.
+/// A reload, a change of the addresses of a host or of the servers
+/// from the admin API builds a new pool, the connections already open
+/// keep using the pool they started with.
+struct Pool {
+    /// the servers of the balancer with the relative weights
+    servers: Vec<(SocketAddress, usize)>,
+    balancer: Arc<dyn LoadBalancer>,
+    states: Arc<BackendStates>,
+    options: Arc<ProxyOptions>,
+    mode: ProxyMode,
+    /// the health checks of the servers of this pool, None if disabled
+    health_check: Option<HealthCheckConfig>
+}
+
+impl Pool {
+    /// Build the pool, taking the error pages from the configuration.
+    /// # Arguments
+    ///
+    /// * `config` - the configuration
+    /// * `resolved` - the addresses of the hosts of the configuration, in the same order
+    /// * `previous` - the old pool on a reload, the states of the
+    ///   servers that are in both the pools are kept
+    ///
+    /// # Return
+    ///
+    /// * The pool, or the error if the balancer refused the servers or the options
+    fn new(config: &mut Config, resolved: &[Vec<IpAddr>], previous: Option<&Pool>) -> Result<Self, ConfigError> {
+        let options = Arc::new(ProxyOptions {
+            retries: config.retries,
+            timeouts: config.timeouts,
+            error_pages: std::mem::take(&mut config.error_pages)
+        });
+        Self::build(config, resolved, options, previous)
+    }
+
+    /// Build a pool with the options of this one and the servers of the
+    /// configuration, after a change of the addresses of a host or from
+    /// the admin API. The states of the servers that are in both the pools are kept.
+    fn rebuild(&self, config: &Config, resolved: &[Vec<IpAddr>]) -> Result<Self, ConfigError> {
+        Self::build(config, resolved, Arc::clone(&self.options), Some(self))
+    }
+
+    /// Build the pool with the servers of the configuration
+    /// followed by the addresses of the hosts. The servers that are
+    /// also in the previous pool keep their states and the state
+    /// in the balancer, e.g. the in-flight connections.
+    fn build(
+        config: &Config,
+        resolved: &[Vec<IpAddr>],
+        options: Arc<ProxyOptions>,
+        previous: Option<&Pool>
+    ) -> Result<Self, ConfigError> {
+        let (host_servers, host_checks) = dns::expand(&config.hosts, resolved);
+        let indexed_servers: Vec<_> = config.server_indexes()
+            .zip(config.servers.iter().cloned())
+            .chain(host_servers)
+            .collect();
+        let servers: Vec<_> = indexed_servers.iter().map(|(_, server)| server.clone()).collect();
+        let health_check = config.health_check.clone().map(|mut health_check| {
+            health_check.http_checks.extend(host_checks);
+            health_check
+        });
+        let mut states = BackendStates::new(&servers)
+            .with_outlier_detection(config.outlier_detection.clone());
+        let mut balancer = create_balancer(config.algorithm, indexed_servers, &config.algorithm_options)?;
+        if let Some(previous) = previous {
+            states = states.with_previous(&previous.states);
+            balancer.inherit(previous.balancer.as_ref());
+        }
+
+        Ok(Pool {
+            servers,
+            balancer: Arc::from(balancer),
+            states: Arc::new(states),
+            options,
+            mode: config.mode,
+            health_check
+        })
+    }
+
+    /// Start the health checks of the servers of this pool, if enabled
+    fn spawn_health_checks(&self) -> Option<JoinHandle<()>> {
+        self.health_check.clone().map(|health_check| {
+            tokio::spawn(run_health_checks(Arc::clone(&self.states), health_check))
+        })
+    }
+
+    /// Return true if the server is in this pool
+    fn contains(&self, socket_address: &SocketAddress) -> bool {
+        self.servers.iter().any(|(server, _)| server == socket_address)
+    }
+}
 
 
 /// Manage the app execution
 pub struct Server {
-    listening_socket_addr: SocketAddress
+    config: Config,
+    /// resolves the hosts of the configuration
+    resolver: Arc<dyn Resolver>,
+    /// the current addresses of the hosts of the configuration
+    resolved: Vec<Vec<IpAddr>>
 }
 
 impl Server {
-    pub fn new(socket_addr: SocketAddress) -> Self {
+    pub fn new(config: Config) -> Self {
         Server { 
-            listening_socket_addr: socket_addr
+            config,
+            resolver: Arc::new(SystemResolver),
+            resolved: Vec::new()
         }
     }
 
-    /// Starts the server.
+    /// Return the server with another resolver of the hostnames
+    #[allow(dead_code)]
+    pub fn with_resolver(mut self, resolver: Arc<dyn Resolver>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Starts the server and runs it until the shutdown future completes,
+    /// then stops accepting and drains the active connections.
     /// # Arguments
-    /// 
-    /// * `servers` - a vector with tuples (socket address, relative weight)
-    /// 
-    /// # Generics
-    /// 
-    /// * `T` - load balancer type
-    pub async fn run<T>(&mut self, servers: Vec<(SocketAddress, usize)>)
-    where T: LoadBalancer + Sync + Send + 'static {
-        let balancer = Arc::new(create_and_fill_the_balancer::<T>(servers));
-
-        println!("Starting the server...");
+    ///
+    /// * `shutdown` - completes when the server must shut down
+    /// * `reloads` - the new configurations to apply, e.g. on SIGHUP
+    ///
+    /// # Return
+    ///
+    /// * The errors if a host can't be resolved or the balancer
+    ///   refused the configured servers or options
+    pub async fn run(&mut self, shutdown: impl Future<Output = ()>, reloads: mpsc::Receiver<Config>) -> Result<(), Vec<ConfigError>> {
+        let resolutions = dns::resolve_hosts(&self.config.hosts, &*self.resolver).await?;
+        self.resolved = resolutions.iter().map(|resolution| resolution.addresses.clone()).collect();
+        let mut pool = Arc::new(Pool::new(&mut self.config, &self.resolved, None).map_err(|error| vec![error])?);
+        let mut health_checks = pool.spawn_health_checks();
+        let mut refresher = HostRefresher::spawn(
+            &self.config.hosts, resolutions, Arc::clone(&self.resolver), self.config.dns
+        );
+
+        log_info!("Starting the server...");
     
-        let listener = match TcpListener::bind(self.listening_socket_addr.get())
-        .await {
+        let listener = match bind(&self.config.listen_to, self.config.dual_stack) {
             Ok(listener) => listener,
             Err(e) => panic!("{e}")
         };
 
-        println!("Startup completed...\nListening on {}...", self.listening_socket_addr.get());
+        // the requests of the admin API are applied between two accepted connections
+        let (admin_sender, mut admin_commands) = mpsc::channel(16);
+        let admin = self.config.admin.as_ref().map(|admin| {
+            let listener = match bind(admin, true) {
+                Ok(listener) => listener,
+                Err(e) => panic!("admin: {e}")
+            };
+            log_info!("Admin API listening on {}...", admin);
+            tokio::spawn(admin::serve(listener, admin_sender))
+        });
+
+        log_info!("Startup completed...\nListening on {}...", self.config.listen_to);
+
+        let connections = Arc::new(Connections::new());
+        let mut reloads = resolve_reloads(reloads, Arc::clone(&self.resolver));
+        tokio::pin!(shutdown);
     
         loop {
-            let balancer = Arc::clone(&balancer);
-
-            let socket = match listener.accept().await {
-                Ok((socket, _)) => socket,
-                Err(e) => {
-                    println!("{e}");
+            let (socket, client_address) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        log_error!("accept: {e}");
+                        continue
+                    }
+                },
+                Some((config, resolutions)) = reloads.recv() => {
+                    self.reload(config, resolutions, &mut pool, &mut health_checks, &mut refresher);
                     continue
-                }
+                },
+                update = refresher.next() => {
+                    self.update_host(update, &mut pool, &mut health_checks);
+                    continue
+                },
+                Some(command) = admin_commands.recv() => {
+                    let response = self.admin(command.request, &mut pool, &mut health_checks);
+                    let _ = command.reply.send(response);
+                    continue
+                },
+                _ = &mut shutdown => break
             };
 
-            tokio::spawn(async move {
-                process(socket, balancer.next_server()).await;
-            });
+            log_debug!("client: {} - accepted", client_address); // log
+            let pool = Arc::clone(&pool);
+            let context = RequestContext::new(client_address)
+                .with_states(Arc::clone(&pool.states));
+            let connection_guard = connections.track();
+            let connections = Arc::clone(&connections);
+
+            tokio::spawn(connection_guard.run(async move {
+                let options = &pool.options;
+                let connection = async {
+                    match pool.mode {
+                        ProxyMode::Tcp => process(socket, &*pool.balancer, context, options).await,
+                        ProxyMode::Http => process_http(
+                            socket, &*pool.balancer, context, options, &connections
+                        ).await
+                    }
+                    Ok(())
+                };
+                let lifetime = options.timeouts.lifetime;
+                if let Err(error) = with_timeout(lifetime, TimeoutKind::Lifetime, connection).await {
+                    log_warn!("client: {} - {}", client_address, error); // log
+                }
+            }));
+        }
+
+        // no new connections during the drain
+        drop(listener);
+        if let Some(admin) = admin {
+            admin.abort();
+        }
+        log_info!(
+            "Draining {} connections for at most {:?}...",
+            connections.active(), self.config.drain_timeout
+        );
+        let report = connections.drain(self.config.drain_timeout).await;
+        log_info!(
+            "Shutdown completed: {} connections drained, {} aborted",
+            report.drained, report.aborted
+        );
+        Ok(())
+    }
+
+    /// Apply a new configuration to the new connections.
+    /// If a host can't be resolved or the new pool can't be built
+    /// the old configuration is kept. The servers that are in both
+    /// the configurations keep their state (down, ejected, drained).
+    /// The listener can't change without a restart, the algorithm can.
+    /// # Arguments
+    ///
+    /// * `config` - the new configuration
+    /// * `resolutions` - the resolutions of the hosts of the new configuration
+    /// * `pool` - the current pool, replaced by the new one
+    /// * `health_checks` - the current health checks task, replaced by the new one
+    /// * `refresher` - the current resolutions of the hosts, replaced by the new ones
+    fn reload(
+        &mut self,
+        mut config: Config,
+        resolutions: Result<Vec<Resolution>, Vec<ConfigError>>,
+        pool: &mut Arc<Pool>,
+        health_checks: &mut Option<JoinHandle<()>>,
+        refresher: &mut HostRefresher
+    ) {
+        if config.listen_to != self.config.listen_to {
+            log_warn!("reload: \"Listen_to\" can't change without a restart, it's ignored"); // log
+            config.listen_to = self.config.listen_to.clone();
+        }
+        if config.dual_stack != self.config.dual_stack {
+            log_warn!("reload: \"dual_stack\" can't change without a restart, it's ignored"); // log
+            config.dual_stack = self.config.dual_stack;
+        }
+        if config.admin != self.config.admin {
+            log_warn!("reload: \"Admin\" can't change without a restart, it's ignored"); // log
+            config.admin = self.config.admin.clone();
+        }
+
+        let resolutions = match resolutions {
+            Ok(resolutions) => resolutions,
+            Err(errors) => {
+                log_error!("reload: invalid configuration, the old one is kept:"); // log
+                for error in errors {
+                    log_error!("  {}", error); // log
+                }
+                return
+            }
+        };
+        let resolved: Vec<_> = resolutions.iter().map(|resolution| resolution.addresses.clone()).collect();
+        let new_pool = match Pool::new(&mut config, &resolved, Some(pool)) {
+            Ok(new_pool) => new_pool,
+            Err(e) => {
+                log_error!("reload: invalid configuration, the old one is kept: {}", e); // log
+                return
+            }
+        };
+        log_info!(
+            "reload: configuration applied, {} servers, algorithm {}",
+            new_pool.servers.len(), config.algorithm.name()
+        ); // log
+        Self::replace_pool(new_pool, pool, health_checks);
+        *refresher = HostRefresher::spawn(&config.hosts, resolutions, Arc::clone(&self.resolver), config.dns);
+        self.resolved = resolved;
+        self.config = config;
+    }
+
+    /// Apply the new addresses of a host to the new connections.
+    /// If the new pool can't be built (e.g. too many servers)
+    /// the old addresses are kept.
+    /// # Arguments
+    ///
+    /// * `update` - the new addresses of the host
+    /// * `pool` - the current pool, replaced by the new one
+    /// * `health_checks` - the current health checks task, replaced by the new one
+    fn update_host(&mut self, update: HostUpdate, pool: &mut Arc<Pool>, health_checks: &mut Option<JoinHandle<()>>) {
+        let host = &self.config.hosts[update.index].host;
+        let mut resolved = self.resolved.clone();
+        resolved[update.index] = update.addresses;
+        let new_pool = match pool.rebuild(&self.config, &resolved) {
+            Ok(new_pool) => new_pool,
+            Err(e) => {
+                log_error!("dns: the new addresses of {} can't be applied, the old ones are kept: {}", host, e); // log
+                return
+            }
+        };
+        log_info!("dns: the addresses of {} changed, {} servers", host, new_pool.servers.len()); // log
+        Self::replace_pool(new_pool, pool, health_checks);
+        self.resolved = resolved;
+    }
+
+    /// Apply a request of the admin API. The changes of the servers
+    /// replace the servers of the configuration until the next reload,
+    /// the servers with a hostname can only be drained.
+    /// # Arguments
+    ///
+    /// * `request` - the request of the admin API
+    /// * `pool` - the current pool, replaced by the new one if the servers change
+    /// * `health_checks` - the current health checks task, replaced by the new one
+    ///
+    /// # Return
+    ///
+    /// * The response to send back
+    fn admin(&mut self, request: AdminRequest, pool: &mut Arc<Pool>, health_checks: &mut Option<JoinHandle<()>>) -> AdminResponse {
+        let mut servers = self.config.servers.clone();
+        let result = match request {
+            AdminRequest::List => {
+                let backends = pool.servers.iter()
+                    .map(|(socket_address, weight)| self.backend(pool, socket_address, *weight))
+                    .collect();
+                return AdminResponse::ok(Value::Array(backends))
+            },
+            AdminRequest::Stats => {
+                let timeouts: Map<String, Value> = TimeoutKind::ALL.iter()
+                    .map(|kind| (kind.key().to_string(), json!(kind.count())))
+                    .collect();
+                let total_ejections: usize = pool.servers.iter()
+                    .filter_map(|(socket_address, _)| pool.states.get(socket_address))
+                    .map(|state| state.total_ejections())
+                    .sum();
+                return AdminResponse::ok(json!({ "timeouts": timeouts, "total_ejections": total_ejections }))
+            },
+            AdminRequest::Drain(socket_address) => return self.drain(pool, &socket_address, true),
+            AdminRequest::Undrain(socket_address) => return self.drain(pool, &socket_address, false),
+            AdminRequest::Add { socket_address, .. } if pool.contains(&socket_address) => {
+                return AdminResponse::error(409, admin::DUPLICATE_SERVER)
+            },
+            AdminRequest::Add { socket_address, weight } => {
+                servers.push((socket_address.clone(), weight));
+                self.replace_servers(servers, pool, health_checks).map(|()| {
+                    log_info!("admin: {} added with weight {}", socket_address, weight); // log
+                    AdminResponse { status: 201, body: self.backend(pool, &socket_address, weight) }
+                })
+            },
+            AdminRequest::Remove(socket_address) => Self::configured(pool, &servers, &socket_address)
+                .and_then(|()| {
+                    servers.retain(|(server, _)| *server != socket_address);
+                    self.replace_servers(servers, pool, health_checks)
+                })
+                .map(|()| {
+                    log_info!("admin: {} removed", socket_address); // log
+                    AdminResponse::ok(json!({ "address": socket_address }))
+                }),
+            AdminRequest::Reweight { socket_address, weight } => Self::configured(pool, &servers, &socket_address)
+                .and_then(|()| {
+                    servers.iter_mut()
+                        .filter(|(server, _)| *server == socket_address)
+                        .for_each(|(_, server_weight)| *server_weight = weight);
+                    self.replace_servers(servers, pool, health_checks)
+                })
+                .map(|()| {
+                    log_info!("admin: {} has weight {}", socket_address, weight); // log
+                    AdminResponse::ok(self.backend(pool, &socket_address, weight))
+                })
+        };
+        result.unwrap_or_else(|response| response)
+    }
+
+    /// Check that a server is in the pool and isn't an address of a hostname
+    fn configured(pool: &Pool, servers: &[(SocketAddress, usize)], socket_address: &SocketAddress) -> Result<(), AdminResponse> {
+        if !pool.contains(socket_address) {
+            return Err(AdminResponse::error(404, admin::UNKNOWN_SERVER))
+        }
+        if !servers.iter().any(|(server, _)| server == socket_address) {
+            return Err(AdminResponse::error(409, admin::HOST_SERVER))
+        }
+        Ok(())
+    }
+
+    /// Drain a server of the pool or return it in the pool.
+    /// The state is shared with the next pools, so it survives
+    /// the changes of the other servers.
+    fn drain(&self, pool: &Pool, socket_address: &SocketAddress, drained: bool) -> AdminResponse {
+        let Some(state) = pool.states.get(socket_address) else {
+            return AdminResponse::error(404, admin::UNKNOWN_SERVER)
+        };
+        if state.is_drained() != drained {
+            state.set_drained(drained);
+            log_info!("admin: {} {}", socket_address, if drained { "drained" } else { "returned in the pool" }); // log
+        }
+        let weight = pool.servers.iter()
+            .find(|(server, _)| server == socket_address)
+            .map_or(0, |(_, weight)| *weight);
+        AdminResponse::ok(self.backend(pool, socket_address, weight))
+    }
+
+    /// Replace the servers of the configuration and the pool.
+    /// If the new pool can't be built (e.g. no servers left)
+    /// the old servers are kept.
+    fn replace_servers(
+        &mut self,
+        servers: Vec<(SocketAddress, usize)>,
+        pool: &mut Arc<Pool>,
+        health_checks: &mut Option<JoinHandle<()>>
+    ) -> Result<(), AdminResponse> {
+        let old_servers = std::mem::replace(&mut self.config.servers, servers);
+        match pool.rebuild(&self.config, &self.resolved) {
+            Ok(new_pool) => {
+                Self::replace_pool(new_pool, pool, health_checks);
+                Ok(())
+            },
+            Err(e) => {
+                self.config.servers = old_servers;
+                Err(AdminResponse::error(409, &e.to_string()))
+            }
+        }
+    }
+
+    /// Return a server of the pool and its state, as returned by the admin API
+    fn backend(&self, pool: &Pool, socket_address: &SocketAddress, weight: usize) -> Value {
+        let host = self.config.hosts.iter().zip(&self.resolved)
+            .find(|(host, addresses)| addresses.iter().any(|ip| host.socket_address(ip) == *socket_address))
+            .map(|(host, _)| host.host.as_str());
+        let state = pool.states.get(socket_address);
+        json!({
+            "address": socket_address,
+            "weight": weight,
+            "host": host,
+            "available": pool.states.is_available(socket_address),
+            "healthy": state.is_none_or(|state| state.is_healthy()),
+            "ejected": state.is_some_and(|state| state.is_ejected()),
+            "drained": state.is_some_and(|state| state.is_drained()),
+            "total_ejections": state.map_or(0, |state| state.total_ejections())
+        })
+    }
+
+    /// Replace the pool of the new connections and restart the health checks
+    fn replace_pool(new_pool: Pool, pool: &mut Arc<Pool>, health_checks: &mut Option<JoinHandle<()>>) {
+        if let Some(health_checks) = health_checks.take() {
+            health_checks.abort();
+        }
+        *health_checks = new_pool.spawn_health_checks();
+        *pool = Arc::new(new_pool);
+    }
+}
+
+
+/// Resolve the hosts of the new configurations in a task, so the
+/// accepted connections don't wait for the DNS, and send them on
+/// in the same order.
+/// # Arguments
+///
+/// * `reloads` - the channel of the new configurations
+/// * `resolver` - the resolver of the hostnames
+fn resolve_reloads(mut reloads: mpsc::Receiver<Config>, resolver: Arc<dyn Resolver>) -> mpsc::Receiver<ResolvedConfig> {
+    let (sender, resolved) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while let Some(config) = reloads.recv().await {
+            let resolutions = dns::resolve_hosts(&config.hosts, &*resolver).await;
+            if sender.send((config, resolutions)).await.is_err() {
+                return
+            }
         }
+    });
+    resolved
+}
+
+/// Binds the listener of the server.
+/// # Arguments
+///
+/// * `listen_to` - the socket address on which to listen
+/// * `dual_stack` - for an IPv6 address, if true the listener accepts also
+///   the IPv4 connections (e.g. on `::`), otherwise only the IPv6 ones
+fn bind(listen_to: &SocketAddress, dual_stack: bool) -> io::Result<TcpListener> {
+    let address = listen_to.to_socket_addr();
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, Some(Protocol::TCP))?;
+    if address.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
     }
+    // the same of TcpListener::bind, for restarting while old connections are in TIME_WAIT
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
 }
 
 
-/// Process the request.
-/// Reads the bytes of the request and redirects them to a server 
-/// that will process them and send the response. Finally reads 
-/// the response and redirects it back to the original sender.
+/// Opens a connection to a server chosen by the balancer.
+/// If the connection fails, the server is excluded from the context
+/// and the balancer is asked for another one, while there are retries left.
+/// # Arguments
+///
+/// * `balancer` - the load balancer used to choose the server.
+/// * `context` - information about the connection, passed to the balancer.
+/// * `retries_left` - the retries that can still be done, decreased at every retry.
+/// * `timeouts` - the timeouts configuration, for the connect timeout.
+///
+/// # Return
+///
+/// * The chosen server and the opened connection, or an error
+///   if no server is available or every attempt failed.
+async fn connect<'a>(
+    balancer: &'a dyn LoadBalancer,
+    context: &mut RequestContext,
+    retries_left: &mut usize,
+    timeouts: &TimeoutConfig
+) -> Result<(ServerGuard<'a>, TcpStream), ConnectError> {
+    loop {
+        let server = match balancer.next_server(context) {
+            Some(socket_address) => ServerGuard::new(balancer, socket_address, context),
+            None => {
+                log_warn!("no available server for {}", context.client_address()); // log
+                return Err(ConnectError::NoServer)
+            }
+        };
+        let connecting = TcpStream::connect(server.socket_address.to_socket_addr());
+        let error = match with_timeout(timeouts.connect, TimeoutKind::Connect, connecting).await {
+            Ok(receiver_socket) => return Ok((server, receiver_socket)),
+            Err(error) => error
+        };
+        server.report_result(false);
+        log_warn!("used_socket: {} - failed to open client socket: {}", server.socket_address, error); // log
+        if *retries_left == 0 {
+            return Err(ConnectError::Failed(error));
+        }
+        *retries_left -= 1;
+        context.exclude(server.socket_address.clone());
+        log_info!("used_socket: {} - retrying on another server", server.socket_address); // log
+    }
+}
+
+/// Process the connection.
+/// Opens a connection to the chosen server and pumps the bytes
+/// in both directions concurrently, until both sides have closed
+/// their write half. A half-close on one side is propagated to
+/// the other one, so the balancer works as a plain L4 TCP proxy
+/// for any protocol.
 /// # Arguments
 ///
 /// * `sender_socket` - the sender socket.
-/// * `socket_address` -  the socket address of the server to which 
-///                       to redirect the sender's request.
-async fn process(mut sender_socket: TcpStream, socket_address: &SocketAddress) {
-    let mut buf = vec![0u8; INITIAL_BUFFER_SIZE];
-    let string_soc_addr = socket_address.get();
+/// * `balancer` - the load balancer used to choose the server, the connect
+///   latency is reported to it.
+/// * `context` - information about the connection, passed to the balancer.
+/// * `options` - the retries and the timeouts of both the sides.
+async fn process(
+    sender_socket: TcpStream,
+    balancer: &dyn LoadBalancer,
+    mut context: RequestContext,
+    options: &ProxyOptions
+) {
+    let timeouts = &options.timeouts;
+    let mut retries_left = options.retries.max_retries;
+    let (server, receiver_socket) = match connect(
+        balancer, &mut context, &mut retries_left, timeouts
+    ).await {
+        Ok(connected) => connected,
+        Err(_) => return
+    };
+    server.report_latency();
 
-    print!("used_socket: {} - ", string_soc_addr); // log
+    let mut sender_socket = TimedStream::new(sender_socket, Side::Client, timeouts);
+    let mut receiver_socket = TimedStream::new(receiver_socket, Side::Backend, timeouts);
+    match copy_bidirectional(&mut sender_socket, &mut receiver_socket).await {
+        Ok((bytes_sent, bytes_received)) => {
+            server.report_result(true);
+            log_info!(
+                "used_socket: {} - bytes_sent: {} - bytes_received: {}",
+                server.socket_address, bytes_sent, bytes_received
+            ) // log
+        },
+        Err(error) => {
+            // the errors and the timeouts of the client aren't failures of the server
+            if Side::of(&error) == Some(Side::Backend) {
+                server.report_result(false);
+            }
+            log_warn!("used_socket: {} - proxy error: {}", server.socket_address, error) // log
+        }
+    }
+}
 
-    let total_bytes = read_in_loop(&mut sender_socket, &mut buf).await;
-    println!("bytes_read: {}", total_bytes); // log
+/// Process an HTTP/1.1 connection.
+/// Reads the requests one by one, respecting their framing, and sends
+/// each of them to a server chosen by the balancer. The response is
+/// forwarded back and the connection is kept open while both the
+/// client and the response allow it, and the server isn't shutting down.
+/// If a request can't be forwarded,
+/// the client gets an error response: 503 if no server is available,
+/// 504 on a server timeout, 502 on any other server failure.
+/// # Arguments
+///
+/// * `sender_socket` - the sender socket.
+/// * `balancer` - the load balancer used to choose the server of each request.
+/// * `context` - information about the connection, passed to the balancer.
+/// * `options` - the retries, the timeouts of both the sides and the error pages.
+/// * `connections` - the active connections, for knowing when the shutdown starts.
+async fn process_http(
+    sender_socket: TcpStream,
+    balancer: &dyn LoadBalancer,
+    context: RequestContext,
+    options: &ProxyOptions,
+    connections: &Connections
+) {
+    let (retries, timeouts, error_pages) = (options.retries, &options.timeouts, &options.error_pages);
+    let (sender_reader, sender_writer) = sender_socket.into_split();
+    let (sender_reader, mut sender_writer) = TimedStream::halves(sender_reader, sender_writer, Side::Client, timeouts);
+    let mut sender_reader = BufReader::new(sender_reader);
 
-    match TcpStream::connect(string_soc_addr).await {
-        Ok(mut receiver_socket) => {
-            receiver_socket.write_all(&buf[..total_bytes]).await.unwrap_or_else(|error| {
-                eprintln!("receiver write_all error: {}", error); // log
+    let mut first_request = true;
+    loop {
+        let request = tokio::select! {
+            request = http::read_head(&mut sender_reader) => request,
+            // an idle keep-alive connection is closed on shutdown
+            _ = connections.draining(), if !first_request => return
+        };
+        first_request = false;
+        let request = match request {
+            Ok(Some(request)) => request,
+            Ok(None) => return,
+            Err(error) if TimeoutKind::of(&error).is_some() => {
+                log_warn!("client: {} - {}", context.client_address(), error); // log
                 return
-            });
-            let total_bytes_2 = read_in_loop(&mut receiver_socket, &mut buf).await;
-            if total_bytes_2 == 0 {
+            },
+            Err(error) => {
+                log_warn!("bad request: {}", error); // log
+                let _ = http::write_error_response(&mut sender_writer, 400, error_pages, true).await;
                 return
             }
-            sender_socket.write_all(&buf[..total_bytes_2]).await.unwrap_or_else(|error| {
-                eprintln!("sender write_all error: {}", error); // log
+        };
+        let with_body = request.method() != "HEAD";
+        let request_length = match request.body_length(true) {
+            Ok(request_length) => request_length,
+            Err(error) => {
+                log_warn!("bad request: {}", error); // log
+                let _ = http::write_error_response(&mut sender_writer, 400, error_pages, with_body).await;
                 return
-            });
-        },
-        Err(_) => {
-            eprintln!("failed to open client socket"); // log
-            return
+            }
+        };
+        // the body is read from the client while it's sent,
+        // so only the requests without body can be sent again
+        let retry_on_reset = retries.retry_on_reset
+            && request.is_idempotent()
+            && request_length == http::BodyLength::Empty;
+
+        // the servers excluded by the retries are valid only for this request
+        let mut context = context.clone();
+        let mut retries_left = retries.max_retries;
+        let (server, outcome) = loop {
+            let (server, receiver_socket) = match connect(
+                balancer, &mut context, &mut retries_left, timeouts
+            ).await {
+                Ok(connected) => connected,
+                Err(error) => {
+                    let status = match error {
+                        ConnectError::NoServer => 503,
+                        ConnectError::Failed(error) => error_status(&error)
+                    };
+                    let _ = http::write_error_response(&mut sender_writer, status, error_pages, with_body).await;
+                    return
+                }
+            };
+            let outcome = forward_request(
+                &request, &mut sender_reader, &mut sender_writer,
+                &server, receiver_socket, options, retry_on_reset
+            ).await;
+
+            if matches!(outcome, Ok(HttpOutcome::Reset)) && retries_left > 0 {
+                retries_left -= 1;
+                context.exclude(server.socket_address.clone());
+                log_info!(
+                    "used_socket: {} - {} {} - connection reset, retrying on another server",
+                    server.socket_address, request.method(), request.start_line.1
+                ); // log
+                continue;
+            }
+            break (server, outcome);
+        };
+
+        match outcome {
+            Ok(HttpOutcome::KeepAlive) if request.keep_alive() && !connections.is_draining() => continue,
+            Ok(HttpOutcome::Reset) => {
+                log_warn!(
+                    "used_socket: {} - {} {} - error: {}",
+                    server.socket_address, request.method(), request.start_line.1, http::UNEXPECTED_EOF
+                ); // log
+                let _ = http::write_error_response(&mut sender_writer, 502, error_pages, with_body).await;
+                return
+            },
+            Ok(_) => return,
+            Err(error) => {
+                log_warn!(
+                    "used_socket: {} - {} {} - error: {}",
+                    server.socket_address, request.method(), request.start_line.1, error
+                ); // log
+                return
+            }
         }
-    };
+    }
 }
 
-/// Reads data from socket until all data are arrived
+/// Forwards a single request to a server and its response back to the client.
+/// The request body is sent while the response is read, so interim
+/// responses (e.g. "100 Continue") reach the client in time.
+/// If the server fails before the final response head, an error
+/// response is sent to the client instead.
 /// # Arguments
 ///
-/// * `socket` - the socket from which to read the data.
-/// * `buf` - the buffer in which to save the read data.
-/// 
-/// # Return
-///
-/// * The number of bytes read.
-/// 
-/// # Example
-/// ```
-/// buf.len = 8000
-/// m = 0
-/// 
-/// socket -> sends 8001 Byte
-/// 
-///  read  -> n = 8000 -> buf[m..7999] = buf[0..7999]
-///           buf.len = 8000 * 2 = 16000
-///           m = n + m = 8000 + 0 = 8000
-/// 
-///  read  -> n = 1 -> buf[m..m+1] = buf[8000..8001]
-///           n < (buf.len() - m) = 1 < 16000 - 8000 = 1 < 8000 = true
-///              return n + m = 1 + 8000
-/// ```
-async fn read_in_loop(socket: &mut TcpStream, buf: &mut Vec<u8>) -> usize {
-    let mut m = 0;
-    loop {
-        m = match (*socket).read(&mut buf[m..]).await {
-            Ok(n) if n <= 0 => break m,
-            Ok(n) if n < (buf.len() - m) => break n + m,
-            Ok(n) => {
-                buf.resize(buf.len() * 2, 0u8);
-                n + m
-            },
-            Err(e) => {
-                eprintln!("failed to read from socket; err = {:?}", e);
-                return 0;
+/// * `request` - the head of the request, already read.
+/// * `sender_reader` - the client side from which to read the request body.
+/// * `sender_writer` - the client side on which to write the response.
+/// * `server` - the chosen server, the latency from the connect to
+///   the first response head is reported to the balancer. Response read
+///   errors and 5xx responses are reported to the outlier detection.
+/// * `receiver_socket` - the connection to the server.
+/// * `options` - the timeouts of the server side and the error pages.
+/// * `retry_on_reset` - if true, a connection closed by the server before
+///   any response byte gives `HttpOutcome::Reset` instead of an error.
+async fn forward_request<R, W>(
+    request: &http::Head,
+    sender_reader: &mut R,
+    sender_writer: &mut W,
+    server: &ServerGuard<'_>,
+    receiver_socket: TcpStream,
+    options: &ProxyOptions,
+    retry_on_reset: bool
+) -> io::Result<HttpOutcome>
+where R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin {
+    let timeouts = &options.timeouts;
+    // already validated by the caller
+    let request_length = request.body_length(true)?;
+    let (receiver_reader, receiver_writer) = receiver_socket.into_split();
+    let (receiver_reader, mut receiver_writer) = TimedStream::halves(receiver_reader, receiver_writer, Side::Backend, timeouts);
+    // the server can answer only after the whole request
+    receiver_reader.defer_first_byte();
+    let mut receiver_reader = BufReader::new(receiver_reader);
+
+    if let Err(error) = receiver_writer.write_all(&request.forwarded()).await {
+        server.report_result(false);
+        return if retry_on_reset && is_reset(&error) { Ok(HttpOutcome::Reset) } else { Err(error) };
+    }
+
+    let send = async {
+        http::copy_body(sender_reader, &mut receiver_writer, request_length).await?;
+        receiver_writer.flush().await?;
+        receiver_writer.start_first_byte();
+        Ok::<_, io::Error>(())
+    };
+    // true when the head of the final response was sent to the client,
+    // then an error response can't be sent anymore
+    let mut final_head_sent = false;
+    let receive = async {
+        let mut first_head = true;
+        loop {
+            let response = match http::read_head(&mut receiver_reader).await {
+                Ok(Some(response)) => response,
+                Ok(None) => {
+                    server.report_result(false);
+                    if retry_on_reset && first_head {
+                        return Ok(HttpOutcome::Reset);
+                    }
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, http::UNEXPECTED_EOF));
+                },
+                Err(error) => {
+                    server.report_result(false);
+                    if retry_on_reset && first_head && is_reset(&error) {
+                        return Ok(HttpOutcome::Reset);
+                    }
+                    return Err(error);
+                }
+            };
+            if first_head {
+                server.report_latency();
+                first_head = false;
+            }
+
+            let status = response.status();
+            let response_length = if (100..200).contains(&status) {
+                http::BodyLength::Empty
+            } else {
+                // checked before forwarding the head, so an invalid
+                // response can still be replaced by an error response
+                let response_length = response.response_body_length(request.method());
+                server.report_result(status < 500 && response_length.is_ok());
+                final_head_sent = response_length.is_ok();
+                response_length?
+            };
+            sender_writer.write_all(&response.forwarded()).await?;
+            log_info!(
+                "used_socket: {} - {} {} - status: {}",
+                server.socket_address, request.method(), request.start_line.1, status
+            ); // log
+
+            if status == 101 {
+                sender_writer.flush().await?;
+                return Ok(HttpOutcome::Upgraded);
+            }
+            if (100..200).contains(&status) {
+                sender_writer.flush().await?;
+                continue;
+            }
+
+            http::copy_body(&mut receiver_reader, sender_writer, response_length).await?;
+            sender_writer.flush().await?;
+
+            return Ok(match response_length {
+                http::BodyLength::UntilClose => HttpOutcome::Close,
+                _ if !response.keep_alive() => HttpOutcome::Close,
+                _ => HttpOutcome::KeepAlive
+            });
+        }
+    };
+    // an error of the server is answered at once, without waiting
+    // for the rest of the request body
+    let (sent, received) = {
+        tokio::pin!(send, receive);
+        let mut sent = None;
+        let received = loop {
+            tokio::select! {
+                result = &mut send, if sent.is_none() => sent = Some(result),
+                received = &mut receive => break received
             }
         };
+        match (sent, received) {
+            (None, Ok(outcome)) => (send.await, Ok(outcome)),
+            // the request body is dropped with the connection on an error
+            (sent, received) => (sent.unwrap_or(Ok(())), received)
+        }
+    };
+
+    let received = match received {
+        Ok(received) => received,
+        Err(error) => {
+            if !final_head_sent {
+                let with_body = request.method() != "HEAD";
+                let status = error_status(&error);
+                let _ = http::write_error_response(sender_writer, status, &options.error_pages, with_body).await;
+            }
+            return Err(error);
+        }
+    };
+    match (sent, received) {
+        (_, HttpOutcome::Upgraded) => {
+            http::tunnel(sender_reader, sender_writer, &mut receiver_reader, &mut receiver_writer)
+                .await?;
+            Ok(HttpOutcome::Upgraded)
+        },
+        (_, HttpOutcome::Reset) => Ok(HttpOutcome::Reset),
+        // the server answered before reading the whole request body
+        (Err(_), _) => Ok(HttpOutcome::Close),
+        (Ok(()), outcome) => Ok(outcome)
     }
-}
\ No newline at end of file
+}
+
+/// Return the status of the error response for a server failure,
+/// 504 for the server timeouts, 502 for everything else
+fn error_status(error: &io::Error) -> u16 {
+    match TimeoutKind::of(error) {
+        Some(kind) if kind.is_backend() => 504,
+        _ => 502
+    }
+}
+
+/// Return true if the error means that the server closed the connection
+fn is_reset(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::BrokenPipe
+    )
+}
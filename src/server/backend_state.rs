@@ -0,0 +1,329 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, atomic::{AtomicBool, AtomicUsize, Ordering}},
+    time::{Duration, Instant}
+};
+use super::{
+    socket_address::SocketAddress,
+    outlier_detection::OutlierDetectionConfig
+};
+
+
+/// The failures observed by the proxy and the ejections of a server
+#[derive(Debug, Default)]
+struct Outlier {
+    /// consecutive failures
+    failures: usize,
+    /// time of the first of the consecutive failures
+    first_failure: Option<Instant>,
+    /// number of ejections, used for the duration of the next one
+    ejections: u32,
+    /// end of the current ejection, None if the server isn't ejected
+    ejected_until: Option<Instant>,
+    /// end of the last ejection
+    returned_at: Option<Instant>
+}
+
+
+/// The state of a server, shared between the proxy,
+/// the health checks and the balancers
+#[derive(Debug)]
+pub struct BackendState {
+    /// false if the health checks marked the server as down
+    healthy: AtomicBool,
+    /// consecutive successful health checks
+    successes: AtomicUsize,
+    /// consecutive failed health checks
+    failures: AtomicUsize,
+    /// true if the outlier detection ejected the server,
+    /// read without locking the outlier state
+    ejected: AtomicBool,
+    /// total number of ejections, for the stats
+    total_ejections: AtomicUsize,
+    /// true if the server was drained from the admin API,
+    /// its active connections go on but it gets no new ones
+    drained: AtomicBool,
+    outlier: Mutex<Outlier>
+}
+
+impl BackendState {
+    pub fn new() -> Self {
+        // optimistic, servers are up until the checks say otherwise
+        BackendState {
+            healthy: AtomicBool::new(true),
+            successes: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+            ejected: AtomicBool::new(false),
+            total_ejections: AtomicUsize::new(0),
+            drained: AtomicBool::new(false),
+            outlier: Mutex::new(Outlier::default())
+        }
+    }
+
+    /// Return true if the server can receive new connections
+    pub fn is_available(&self) -> bool {
+        self.is_healthy() && !self.is_ejected() && !self.is_drained()
+    }
+
+    /// Return false if the health checks marked the server as down
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// Mark the server as up or down
+    pub fn set_healthy(&self, healthy: bool) {
+        self.healthy.store(healthy, Ordering::Relaxed);
+    }
+
+    /// Return true if the outlier detection ejected the server.
+    /// An expired ejection is ended by `BackendStates::is_available`.
+    pub fn is_ejected(&self) -> bool {
+        self.ejected.load(Ordering::Relaxed)
+    }
+
+    /// Return true if the server was drained
+    pub fn is_drained(&self) -> bool {
+        self.drained.load(Ordering::Relaxed)
+    }
+
+    /// Drain the server or return it in the pool
+    pub fn set_drained(&self, drained: bool) {
+        self.drained.store(drained, Ordering::Relaxed);
+    }
+
+    /// Return the total number of ejections of the server
+    pub fn total_ejections(&self) -> usize {
+        self.total_ejections.load(Ordering::Relaxed)
+    }
+
+    /// Record the result of a health check.
+    /// # Arguments
+    ///
+    /// * `success` - the result of the check
+    /// * `rise` - consecutive successes needed to mark a down server as up
+    /// * `fall` - consecutive failures needed to mark an up server as down
+    ///
+    /// # Return
+    ///
+    /// * Some(new state) if the server changed state, otherwise None
+    pub fn record_check(&self, success: bool, rise: usize, fall: usize) -> Option<bool> {
+        let (counter, other, threshold) = if success {
+            (&self.successes, &self.failures, rise)
+        } else {
+            (&self.failures, &self.successes, fall)
+        };
+        other.store(0, Ordering::Relaxed);
+        let consecutive = counter.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if self.is_healthy() != success && consecutive >= threshold {
+            self.set_healthy(success);
+            return Some(success);
+        }
+        None
+    }
+
+    /// Record a successful connection (or request), it resets the
+    /// consecutive failures of the outlier detection
+    pub fn record_success(&self) {
+        let mut outlier = self.outlier.lock().unwrap();
+        outlier.failures = 0;
+        outlier.first_failure = None;
+    }
+
+    /// Record a failed connection (or request).
+    /// The failures of a server already ejected are ignored.
+    /// # Arguments
+    ///
+    /// * `config` - the outlier detection configuration
+    ///
+    /// # Return
+    ///
+    /// * true if the server reached the consecutive failures
+    ///   needed for being ejected
+    pub fn record_failure(&self, config: &OutlierDetectionConfig) -> bool {
+        let mut outlier = self.outlier.lock().unwrap();
+        if outlier.ejected_until.is_some() {
+            return false;
+        }
+        let now = Instant::now();
+        match outlier.first_failure {
+            Some(first) if now.duration_since(first) <= config.window => outlier.failures += 1,
+            // the failures out of the window don't count
+            _ => {
+                outlier.first_failure = Some(now);
+                outlier.failures = 1;
+            }
+        }
+        outlier.failures >= config.consecutive_failures
+    }
+
+    /// Eject the server from the pool.
+    /// # Arguments
+    ///
+    /// * `config` - the outlier detection configuration
+    ///
+    /// # Return
+    ///
+    /// * The duration of the ejection
+    pub fn eject(&self, config: &OutlierDetectionConfig) -> Duration {
+        let mut outlier = self.outlier.lock().unwrap();
+        let now = Instant::now();
+        // a server that behaved for long enough starts again from the base time
+        if outlier.returned_at.is_some_and(|at| now.duration_since(at) > config.max_ejection_time) {
+            outlier.ejections = 0;
+        }
+        let ejection_time = config.ejection_time(outlier.ejections);
+        outlier.ejections = outlier.ejections.saturating_add(1);
+        outlier.ejected_until = Some(now + ejection_time);
+        outlier.failures = 0;
+        outlier.first_failure = None;
+        self.ejected.store(true, Ordering::Relaxed);
+        self.total_ejections.fetch_add(1, Ordering::Relaxed);
+        ejection_time
+    }
+
+    /// End the ejection of the server if its time is over.
+    /// # Return
+    ///
+    /// * true if this call returned the server in the pool
+    pub fn end_expired_ejection(&self) -> bool {
+        if !self.is_ejected() {
+            return false;
+        }
+        let mut outlier = self.outlier.lock().unwrap();
+        match outlier.ejected_until {
+            Some(until) if Instant::now() >= until => {
+                outlier.ejected_until = None;
+                outlier.returned_at = Some(Instant::now());
+                self.ejected.store(false, Ordering::Relaxed);
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+impl Default for BackendState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// The states of all the configured servers
+#[derive(Debug, Default)]
+pub struct BackendStates {
+    states: HashMap<SocketAddress, Arc<BackendState>>,
+    /// None if the outlier detection is disabled
+    outlier_detection: Option<OutlierDetectionConfig>,
+    /// taken while deciding an ejection, so the max ejection
+    /// percentage is respected by concurrent failures
+    ejection_lock: Mutex<()>
+}
+
+impl BackendStates {
+    /// Create the states of the servers, all of them up.
+    /// # Arguments
+    ///
+    /// * `servers` - vector with socket addresses and relative weights
+    pub fn new(servers: &[(SocketAddress, usize)]) -> Self {
+        BackendStates {
+            states: servers.iter()
+                .map(|(socket_address, _)| (socket_address.clone(), Arc::new(BackendState::new())))
+                .collect(),
+            outlier_detection: None,
+            ejection_lock: Mutex::new(())
+        }
+    }
+
+    /// Return the states with the passive outlier detection enabled
+    pub fn with_outlier_detection(mut self, config: Option<OutlierDetectionConfig>) -> Self {
+        self.outlier_detection = config;
+        self
+    }
+
+    /// Return the states taking the ones of the servers that are also
+    /// in `previous`, so a change of the other servers doesn't bring back
+    /// a server marked down, ejected or drained
+    pub fn with_previous(mut self, previous: &BackendStates) -> Self {
+        for (socket_address, state) in self.states.iter_mut() {
+            if let Some(previous_state) = previous.states.get(socket_address) {
+                *state = Arc::clone(previous_state);
+            }
+        }
+        self
+    }
+
+    /// Return the state of a server, if it's configured
+    pub fn get(&self, socket_address: &SocketAddress) -> Option<&Arc<BackendState>> {
+        self.states.get(socket_address)
+    }
+
+    /// Return true if the server can receive new connections.
+    /// Servers without state are always available.
+    pub fn is_available(&self, socket_address: &SocketAddress) -> bool {
+        self.states.get(socket_address).is_none_or(|state| {
+            Self::end_expired_ejection(socket_address, state);
+            state.is_available()
+        })
+    }
+
+    /// Return an iterator over the servers and their states
+    pub fn iter(&self) -> impl Iterator<Item = (&SocketAddress, &Arc<BackendState>)> {
+        self.states.iter()
+    }
+
+    /// Report a successful connection (or request) to a server,
+    /// used by the outlier detection
+    pub fn report_success(&self, socket_address: &SocketAddress) {
+        if self.outlier_detection.is_none() {
+            return;
+        }
+        if let Some(state) = self.states.get(socket_address) {
+            state.record_success();
+        }
+    }
+
+    /// Report a failed connection (or request) to a server.
+    /// The server is ejected if it reached the consecutive failures
+    /// and the max ejection percentage allows it.
+    pub fn report_failure(&self, socket_address: &SocketAddress) {
+        let (config, state) = match (&self.outlier_detection, self.states.get(socket_address)) {
+            (Some(config), Some(state)) => (config, state),
+            _ => return
+        };
+        if !state.record_failure(config) {
+            return;
+        }
+
+        let _ejection_lock = self.ejection_lock.lock().unwrap();
+        if state.is_ejected() {
+            return;
+        }
+        let ejected = self.states.iter()
+            .filter(|(socket_address, state)| {
+                Self::end_expired_ejection(socket_address, state);
+                state.is_ejected()
+            })
+            .count();
+        if !config.can_eject(ejected, self.states.len()) {
+            log_warn!(
+                "outlier detection: {} not ejected, {}/{} servers already ejected",
+                socket_address, ejected, self.states.len()
+            ); // log
+            return;
+        }
+        let ejection_time = state.eject(config);
+        log_warn!(
+            "outlier detection: {} ejected for {:?} (ejection #{}, {}/{} servers ejected)",
+            socket_address, ejection_time, state.total_ejections(), ejected + 1, self.states.len()
+        ); // log
+    }
+
+    /// End the ejection of a server if its time is over and log it
+    fn end_expired_ejection(socket_address: &SocketAddress, state: &BackendState) {
+        if state.end_expired_ejection() {
+            log_info!("outlier detection: {} returned in the pool", socket_address); // log
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::HashMap,
+    future::{self, Future},
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::Poll,
+    time::Duration
+};
+use tokio::{net::lookup_host, sync::mpsc, task::JoinHandle, time};
+use super::{health_check::HttpCheck, socket_address::SocketAddress};
+use crate::balancers::{config_error::ConfigError, IndexedServer};
+
+// Default values of the DNS configuration
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+// Max time for a single resolution
+pub const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+// Min time between two resolutions of the same host, also with a TTL of 0
+pub const MIN_TTL: Duration = Duration::from_secs(1);
+
+// error messages
+pub static NO_ADDRESSES: &str = "The host has no addresses";
+pub static RESOLVE_TIMED_OUT: &str = "The resolution timed out";
+
+
+/// Configuration of the resolution of the servers with a hostname
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DnsConfig {
+    /// max time between two resolutions of the same host,
+    /// a shorter TTL returned by the resolver is respected
+    pub interval: Duration
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        DnsConfig {
+            interval: DEFAULT_REFRESH_INTERVAL
+        }
+    }
+}
+
+
+/// A server configured with a hostname instead of an ip address.
+/// Every address of the host is a server with the same port, weight
+/// and HTTP check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostServer {
+    /// position of the server in the "Servers" array of the json
+    pub index: usize,
+    pub host: String,
+    pub port: u16,
+    pub weight: usize,
+    /// the HTTP check of every address, None for a TCP connect
+    pub http_check: Option<HttpCheck>
+}
+
+impl HostServer {
+    /// Return the json path of the server, e.g. `Servers[2]`
+    pub fn path(&self) -> String {
+        format!("Servers[{}]", self.index)
+    }
+
+    /// Return the socket address of one of the addresses of the host
+    pub fn socket_address(&self, ip: &IpAddr) -> SocketAddress {
+        SocketAddress::from(SocketAddr::new(*ip, self.port))
+    }
+}
+
+
+/// The addresses of a host returned by a resolver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    /// the addresses, sorted and without duplicates
+    pub addresses: Vec<IpAddr>,
+    /// how long the addresses are valid, None if the resolver doesn't know it
+    pub ttl: Option<Duration>
+}
+
+impl Resolution {
+    pub fn new(mut addresses: Vec<IpAddr>, ttl: Option<Duration>) -> Self {
+        // sorted, so the same records in a different order aren't a change
+        addresses.sort();
+        addresses.dedup();
+        Resolution { addresses, ttl }
+    }
+}
+
+
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = io::Result<Resolution>> + Send + 'a>>;
+
+/// Resolves the hostnames of the servers.
+/// The system resolver is used by default, the tests can use
+/// a resolver with fixed answers.
+pub trait Resolver: Send + Sync {
+    /// Return the A and AAAA records of the host
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a>;
+}
+
+/// The resolver of the operating system, it doesn't return the TTL
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve<'a>(&'a self, host: &'a str) -> ResolveFuture<'a> {
+        Box::pin(async move {
+            let addresses = lookup_host((host, 0)).await?
+                .map(|socket_addr| socket_addr.ip())
+                .collect();
+            Ok(Resolution::new(addresses, None))
+        })
+    }
+}
+
+/// Resolve a host within RESOLVE_TIMEOUT.
+/// # Return
+///
+/// * The addresses of the host, or an error if it has none
+async fn resolve(resolver: &dyn Resolver, host: &str) -> io::Result<Resolution> {
+    let resolution = time::timeout(RESOLVE_TIMEOUT, resolver.resolve(host)).await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, RESOLVE_TIMED_OUT))??;
+    if resolution.addresses.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, NO_ADDRESSES));
+    }
+    Ok(resolution)
+}
+
+
+/// Resolve every host of the configuration, at startup and on reload.
+/// The hosts are resolved concurrently.
+/// # Arguments
+///
+/// * `hosts` - the servers with a hostname
+/// * `resolver` - the resolver of the hostnames
+///
+/// # Return
+///
+/// * The resolution of every host, in the same order, or an error
+///   for every host that can't be resolved
+pub async fn resolve_hosts(hosts: &[HostServer], resolver: &dyn Resolver) -> Result<Vec<Resolution>, Vec<ConfigError>> {
+    // join all the resolutions
+    let mut pending: Vec<_> = hosts.iter().map(|host| Box::pin(resolve(resolver, &host.host))).collect();
+    let mut results: Vec<Option<io::Result<Resolution>>> = hosts.iter().map(|_| None).collect();
+    future::poll_fn(|cx| {
+        let mut done = true;
+        for (resolution, result) in pending.iter_mut().zip(results.iter_mut()) {
+            if result.is_none() {
+                match resolution.as_mut().poll(cx) {
+                    Poll::Ready(resolution) => *result = Some(resolution),
+                    Poll::Pending => done = false
+                }
+            }
+        }
+        if done { Poll::Ready(()) } else { Poll::Pending }
+    }).await;
+
+    let mut resolutions = Vec::with_capacity(hosts.len());
+    let mut errors = Vec::new();
+    for (host, result) in hosts.iter().zip(results) {
+        match result.expect("every resolution is done") {
+            Ok(resolution) => resolutions.push(resolution),
+            Err(error) => errors.push(ConfigError::Resolve {
+                path: format!("{}.host", host.path()),
+                host: host.host.clone(),
+                error
+            })
+        }
+    }
+    if errors.is_empty() { Ok(resolutions) } else { Err(errors) }
+}
+
+/// Return the servers of the resolved hosts, every address with the
+/// position in the json and the weight of its host, and their HTTP checks.
+/// # Arguments
+///
+/// * `hosts` - the servers with a hostname
+/// * `resolved` - the addresses of every host, in the same order
+pub fn expand(hosts: &[HostServer], resolved: &[Vec<IpAddr>]) -> (Vec<IndexedServer>, HashMap<SocketAddress, HttpCheck>) {
+    let mut servers = Vec::new();
+    let mut http_checks = HashMap::new();
+    for (host, addresses) in hosts.iter().zip(resolved) {
+        for ip in addresses {
+            let socket_address = host.socket_address(ip);
+            if let Some(http_check) = &host.http_check {
+                http_checks.insert(socket_address.clone(), http_check.clone());
+            }
+            servers.push((host.index, (socket_address, host.weight)));
+        }
+    }
+    (servers, http_checks)
+}
+
+/// Return the time to wait before resolving a host again,
+/// the TTL if the resolver returned it, but at most the interval
+pub fn refresh_time(ttl: Option<Duration>, interval: Duration) -> Duration {
+    match ttl {
+        Some(ttl) => ttl.max(MIN_TTL).min(interval),
+        None => interval
+    }
+}
+
+
+/// The new addresses of a host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostUpdate {
+    /// position of the host in the hosts of the configuration
+    pub index: usize,
+    pub addresses: Vec<IpAddr>
+}
+
+/// The tasks that periodically resolve again the hosts of a configuration,
+/// one for each host. Dropping it stops the tasks.
+pub struct HostRefresher {
+    updates: mpsc::Receiver<HostUpdate>,
+    tasks: Vec<JoinHandle<()>>
+}
+
+impl HostRefresher {
+    /// Start resolving again every host.
+    /// # Arguments
+    ///
+    /// * `hosts` - the servers with a hostname
+    /// * `resolutions` - the current resolution of every host, in the same order
+    /// * `resolver` - the resolver of the hostnames
+    /// * `config` - the DNS configuration
+    pub fn spawn(hosts: &[HostServer], resolutions: Vec<Resolution>, resolver: Arc<dyn Resolver>, config: DnsConfig) -> Self {
+        let (sender, updates) = mpsc::channel(hosts.len().max(1));
+        let tasks = hosts.iter().zip(resolutions).enumerate().map(|(index, (host, resolution))| {
+            tokio::spawn(refresh_host(
+                index, host.host.clone(), resolution, Arc::clone(&resolver), config.interval, sender.clone()
+            ))
+        }).collect();
+        HostRefresher { updates, tasks }
+    }
+
+    /// Return the next change of the addresses of a host,
+    /// never completes if there are no hosts
+    pub async fn next(&mut self) -> HostUpdate {
+        match self.updates.recv().await {
+            Some(update) => update,
+            None => std::future::pending().await
+        }
+    }
+}
+
+impl Drop for HostRefresher {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Resolve a host every time its addresses expire and send
+/// the new ones when they change. If the host can't be resolved
+/// the last addresses are kept.
+async fn refresh_host(
+    index: usize,
+    host: String,
+    mut current: Resolution,
+    resolver: Arc<dyn Resolver>,
+    interval: Duration,
+    updates: mpsc::Sender<HostUpdate>
+) {
+    loop {
+        time::sleep(refresh_time(current.ttl, interval)).await;
+        match resolve(&*resolver, &host).await {
+            Ok(resolution) => {
+                if resolution.addresses != current.addresses {
+                    log_info!("dns: {} resolved to {:?}", host, resolution.addresses); // log
+                    let update = HostUpdate { index, addresses: resolution.addresses.clone() };
+                    if updates.send(update).await.is_err() {
+                        return
+                    }
+                }
+                current = resolution;
+            },
+            Err(error) => {
+                log_warn!("dns: {} can't be resolved, the last addresses are kept: {}", host, error); // log
+                current.ttl = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::HashMap, io, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time
+};
+use super::{backend_state::BackendStates, http, socket_address::SocketAddress};
+
+// Default values of the health checks configuration
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+pub const DEFAULT_RISE: usize = 2;
+pub const DEFAULT_FALL: usize = 3;
+
+// Default values of the HTTP checks
+pub const DEFAULT_HTTP_PATH: &str = "/";
+pub const DEFAULT_HTTP_METHOD: &str = "GET";
+pub const DEFAULT_HTTP_STATUS: (u16, u16) = (200, 399);
+// Max number of bytes of the response body searched for the expected substring
+pub const MAX_CHECKED_BODY_SIZE: u64 = 64 * 1024;
+
+
+/// Configuration of the active health checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthCheckConfig {
+    /// time between two checks of the same server
+    pub interval: Duration,
+    /// max time for a single check
+    pub timeout: Duration,
+    /// consecutive successes needed to mark a down server as up
+    pub rise: usize,
+    /// consecutive failures needed to mark an up server as down
+    pub fall: usize,
+    /// servers checked with an HTTP request, the others with a TCP connect
+    pub http_checks: HashMap<SocketAddress, HttpCheck>
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        HealthCheckConfig {
+            interval: DEFAULT_INTERVAL,
+            timeout: DEFAULT_TIMEOUT,
+            rise: DEFAULT_RISE,
+            fall: DEFAULT_FALL,
+            http_checks: HashMap::new()
+        }
+    }
+}
+
+
+/// Configuration of the HTTP check of a single server.
+/// The check succeeds if the response status is in the expected range
+/// and the body contains the expected substring, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpCheck {
+    /// path of the request, e.g. "/health"
+    pub path: String,
+    /// method of the request, e.g. "GET"
+    pub method: String,
+    /// value of the Host header, the server socket address if missing
+    pub host: Option<String>,
+    /// inclusive range of the accepted status codes
+    pub status: (u16, u16),
+    /// substring that the response body must contain
+    pub body: Option<String>,
+    /// max time for the check, the global timeout if missing
+    pub timeout: Option<Duration>
+}
+
+impl Default for HttpCheck {
+    fn default() -> Self {
+        HttpCheck {
+            path: DEFAULT_HTTP_PATH.to_string(),
+            method: DEFAULT_HTTP_METHOD.to_string(),
+            host: None,
+            status: DEFAULT_HTTP_STATUS,
+            body: None,
+            timeout: None
+        }
+    }
+}
+
+
+/// Periodically checks every server and marks it up or down in its state.
+/// A server with an HTTP check is checked with `check_http`, the others
+/// succeed if a TCP connection can be opened within the timeout.
+/// Never returns, must be spawned in a task.
+/// # Arguments
+///
+/// * `states` - the states of the servers to check
+/// * `config` - the health checks configuration
+pub async fn run_health_checks(states: Arc<BackendStates>, config: HealthCheckConfig) {
+    let mut interval = time::interval(config.interval);
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        // all the servers are checked concurrently
+        let checks: Vec<_> = states.iter().map(|(socket_address, state)| {
+            let socket_address = socket_address.clone();
+            let state = Arc::clone(state);
+            let http_check = config.http_checks.get(&socket_address).cloned();
+            let (timeout, rise, fall) = (config.timeout, config.rise, config.fall);
+            tokio::spawn(async move {
+                let success = match http_check {
+                    Some(http_check) => check_http(&socket_address, &http_check, timeout).await,
+                    None => check_tcp(&socket_address, timeout).await
+                };
+                match state.record_check(success, rise, fall) {
+                    Some(true) => log_info!("health check: {} is up", socket_address), // log
+                    Some(false) => log_warn!("health check: {} is down", socket_address), // log
+                    None => ()
+                }
+            })
+        }).collect();
+        for check in checks {
+            let _ = check.await;
+        }
+    }
+}
+
+/// Return true if a TCP connection to the server can be opened within the timeout
+pub async fn check_tcp(socket_address: &SocketAddress, timeout: Duration) -> bool {
+    matches!(
+        time::timeout(timeout, TcpStream::connect(socket_address.to_socket_addr())).await,
+        Ok(Ok(_))
+    )
+}
+
+/// Return true if the server answers an HTTP request with the expected
+/// status and body within the timeout.
+/// # Arguments
+///
+/// * `socket_address` - the server to check
+/// * `check` - the request to send and the expected response
+/// * `default_timeout` - max time for the check, if the check has no timeout
+pub async fn check_http(socket_address: &SocketAddress, check: &HttpCheck, default_timeout: Duration) -> bool {
+    let timeout = check.timeout.unwrap_or(default_timeout);
+    matches!(
+        time::timeout(timeout, http_request(socket_address, check)).await,
+        Ok(Ok(true))
+    )
+}
+
+/// Send the request of the check and match the response
+async fn http_request(socket_address: &SocketAddress, check: &HttpCheck) -> io::Result<bool> {
+    let mut stream = TcpStream::connect(socket_address.to_socket_addr()).await?;
+    let host = check.host.clone().unwrap_or_else(|| socket_address.to_string());
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nUser-Agent: load_balancer_rust\r\n\r\n",
+        check.method, check.path, host
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut reader = BufReader::new(stream);
+    let head = match http::read_head(&mut reader).await? {
+        Some(head) => head,
+        None => return Ok(false)
+    };
+    let status = head.status();
+    if status < check.status.0 || status > check.status.1 {
+        return Ok(false);
+    }
+
+    match &check.body {
+        None => Ok(true),
+        Some(expected) => {
+            let length = head.response_body_length(&check.method)?;
+            let body = http::read_body(&mut reader, length, MAX_CHECKED_BODY_SIZE).await?;
+            Ok(String::from_utf8_lossy(&body).contains(expected.as_str()))
+        }
+    }
+}
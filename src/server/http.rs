@@ -0,0 +1,575 @@
+use std::{collections::HashMap, io, path::Path};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt
+};
+
+// Max size of the request/response line plus all the headers
+pub const MAX_HEAD_SIZE: u64 = 64 * 1024;
+// Max size of a single line of a chunked body (chunk size or trailer)
+pub const MAX_CHUNK_LINE_SIZE: u64 = 4 * 1024;
+// Max number of headers in a request/response
+pub const MAX_HEADERS: usize = 100;
+// The headers meaningful only for a single connection (RFC 9110, 7.6.1),
+// removed before forwarding a message. Transfer-Encoding is kept, the body
+// is forwarded with the same framing, and Upgrade is kept if it's listed
+// in the Connection header.
+pub const HOP_BY_HOP_HEADERS: [&str; 5] = [
+    "Keep-Alive", "Proxy-Connection", "Proxy-Authenticate", "Proxy-Authorization", "TE"
+];
+// The tokens of the Connection header forwarded with the message,
+// the others name hop-by-hop headers
+pub const FORWARDED_CONNECTION_TOKENS: [&str; 3] = ["close", "keep-alive", "upgrade"];
+
+// Error messages
+pub static HEAD_TOO_LARGE: &str = "The HTTP head is too large";
+pub static TOO_MANY_HEADERS: &str = "There are too many HTTP headers";
+pub static MALFORMED_START_LINE: &str = "The HTTP start line is malformed";
+pub static MALFORMED_HEADER: &str = "An HTTP header is malformed";
+pub static UNSUPPORTED_VERSION: &str = "The HTTP version isn't supported";
+pub static INVALID_CONTENT_LENGTH: &str = "The Content-Length header isn't valid";
+pub static INVALID_TRANSFER_ENCODING: &str = "The Transfer-Encoding header isn't valid";
+pub static AMBIGUOUS_LENGTH: &str = "The request has both the Transfer-Encoding and the Content-Length headers";
+pub static MALFORMED_CHUNK: &str = "The chunked body is malformed";
+pub static UNEXPECTED_EOF: &str = "The connection was closed in the middle of a message";
+
+
+/// HTTP version of a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    Http10,
+    Http11
+}
+
+/// How the end of a message body is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyLength {
+    /// the message has no body
+    Empty,
+    /// the body is exactly this number of bytes
+    Fixed(u64),
+    /// the body uses the chunked transfer coding
+    Chunked,
+    /// the body ends when the connection is closed (responses only)
+    UntilClose
+}
+
+/// The start line and the headers of an HTTP message.
+/// The raw bytes are kept for the start line, the message is
+/// forwarded without its hop-by-hop headers.
+#[derive(Debug)]
+pub struct Head {
+    /// the three parts of the start line
+    pub start_line: (String, String, String),
+    pub version: Version,
+    pub headers: Vec<(String, String)>,
+    pub raw: Vec<u8>
+}
+
+impl Head {
+
+    /// Return the value of the first header with the given name
+    /// (case insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return true if the comma separated header contains the token
+    /// (case insensitive), e.g. `Connection: keep-alive, Upgrade`.
+    pub fn header_contains(&self, name: &str, token: &str) -> bool {
+        self.headers.iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .flat_map(|(_, value)| value.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    }
+
+    /// Return the method of a request
+    pub fn method(&self) -> &str {
+        &self.start_line.0
+    }
+
+    /// Return the status code of a response, 0 if it isn't valid
+    pub fn status(&self) -> u16 {
+        self.start_line.1.parse().unwrap_or(0)
+    }
+
+    /// Return true if the request method is idempotent (RFC 9110, 9.2.2),
+    /// so the request can be sent again to another server
+    pub fn is_idempotent(&self) -> bool {
+        matches!(self.method(), "GET" | "HEAD" | "OPTIONS" | "TRACE" | "PUT" | "DELETE")
+    }
+
+    /// Return true if the connection should be kept open after this message
+    pub fn keep_alive(&self) -> bool {
+        match self.version {
+            Version::Http11 => !self.header_contains("Connection", "close"),
+            Version::Http10 => self.header_contains("Connection", "keep-alive")
+        }
+    }
+
+    /// Return how the body of this message must be read.
+    /// Transfer-Encoding wins over Content-Length in a response, a request
+    /// with both is refused because the server could frame it differently
+    /// (request smuggling, RFC 9112, 6.1).
+    /// # Arguments
+    ///
+    /// * `is_request` - true if the message is a request, a request without
+    ///   length has no body, a response without length ends on close.
+    pub fn body_length(&self, is_request: bool) -> io::Result<BodyLength> {
+        if let Some(encoding) = self.header("Transfer-Encoding") {
+            if is_request && self.header("Content-Length").is_some() {
+                return Err(invalid_data(AMBIGUOUS_LENGTH));
+            }
+            let chunked_last = encoding.rsplit(',').next()
+                .map(|t| t.trim().eq_ignore_ascii_case("chunked"))
+                .unwrap_or(false);
+            return match (chunked_last, is_request) {
+                (true, _) => Ok(BodyLength::Chunked),
+                (false, true) => Err(invalid_data(INVALID_TRANSFER_ENCODING)),
+                (false, false) => Ok(BodyLength::UntilClose)
+            };
+        }
+
+        let mut lengths = self.headers.iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Content-Length"))
+            .flat_map(|(_, value)| value.split(','))
+            .map(|value| parse_digits(value.trim()));
+        match lengths.next() {
+            Some(Some(first)) => {
+                // repeated values are accepted only if they are all equal
+                for other in lengths {
+                    if other != Some(first) {
+                        return Err(invalid_data(INVALID_CONTENT_LENGTH));
+                    }
+                }
+                Ok(if first == 0 { BodyLength::Empty } else { BodyLength::Fixed(first) })
+            },
+            Some(None) => Err(invalid_data(INVALID_CONTENT_LENGTH)),
+            None if is_request => Ok(BodyLength::Empty),
+            None => Ok(BodyLength::UntilClose)
+        }
+    }
+
+    /// Return the head to forward to the other side: the start line
+    /// and the headers without the hop-by-hop ones. The Connection
+    /// header keeps only the tokens in FORWARDED_CONNECTION_TOKENS and
+    /// Content-Length is removed if Transfer-Encoding is present.
+    pub fn forwarded(&self) -> Vec<u8> {
+        let connection: Vec<&str> = self.headers.iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Connection"))
+            .flat_map(|(_, value)| value.split(','))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .collect();
+        let (kept, removed): (Vec<&str>, Vec<&str>) = connection.into_iter()
+            .partition(|token| FORWARDED_CONNECTION_TOKENS.iter().any(|t| t.eq_ignore_ascii_case(token)));
+        let upgrade = kept.iter().any(|token| token.eq_ignore_ascii_case("upgrade"));
+        let chunked = self.header("Transfer-Encoding").is_some();
+        let is_removed = |key: &str| {
+            HOP_BY_HOP_HEADERS.iter().chain(&removed).any(|header| header.eq_ignore_ascii_case(key))
+                || key.eq_ignore_ascii_case("Connection")
+                || (key.eq_ignore_ascii_case("Upgrade") && !upgrade)
+                || (key.eq_ignore_ascii_case("Content-Length") && chunked)
+        };
+
+        let start_line_end = self.raw.iter().position(|&b| b == b'\n').map_or(self.raw.len(), |i| i + 1);
+        let mut head = self.raw[..start_line_end].to_vec();
+        if !kept.is_empty() {
+            head.extend_from_slice(format!("Connection: {}\r\n", kept.join(", ")).as_bytes());
+        }
+        for (key, value) in self.headers.iter().filter(|(key, _)| !is_removed(key)) {
+            head.extend_from_slice(format!("{}: {}\r\n", key, value).as_bytes());
+        }
+        head.extend_from_slice(b"\r\n");
+        head
+    }
+
+    /// Return how the body of this response must be read.
+    /// # Arguments
+    ///
+    /// * `request_method` - the method of the request that caused the response
+    pub fn response_body_length(&self, request_method: &str) -> io::Result<BodyLength> {
+        let status = self.status();
+        if request_method.eq_ignore_ascii_case("HEAD")
+            || (100..200).contains(&status)
+            || status == 204
+            || status == 304 {
+            return Ok(BodyLength::Empty);
+        }
+        self.body_length(false)
+    }
+}
+
+
+/// Reads the start line and the headers of a message.
+/// # Arguments
+///
+/// * `reader` - the buffered reader from which to read the head.
+///
+/// # Return
+///
+/// * None if the connection was closed before the first byte
+///   of the message, otherwise the parsed head.
+pub async fn read_head<R>(reader: &mut R) -> io::Result<Option<Head>>
+where R: AsyncBufRead + Unpin {
+    let mut raw = Vec::with_capacity(1024);
+    let mut lines: Vec<(usize, usize)> = Vec::new();
+
+    loop {
+        let start = raw.len();
+        let remaining = MAX_HEAD_SIZE - start as u64;
+        if remaining == 0 {
+            return Err(invalid_data(HEAD_TOO_LARGE));
+        }
+        let n = (&mut *reader).take(remaining).read_until(b'\n', &mut raw).await?;
+        if n == 0 {
+            if raw.is_empty() {
+                return Ok(None);
+            }
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, UNEXPECTED_EOF));
+        }
+        if raw[raw.len() - 1] != b'\n' {
+            return Err(invalid_data(HEAD_TOO_LARGE));
+        }
+
+        let end = trim_line_end(&raw, start);
+        if end == start {
+            // empty lines before the start line must be ignored (RFC 9112, 2.2)
+            if lines.is_empty() {
+                raw.clear();
+                continue;
+            }
+            break;
+        }
+        if lines.len() > MAX_HEADERS {
+            return Err(invalid_data(TOO_MANY_HEADERS));
+        }
+        lines.push((start, end));
+    }
+
+    let text = |(start, end): (usize, usize)| {
+        std::str::from_utf8(&raw[start..end]).map_err(|_| invalid_data(MALFORMED_HEADER))
+    };
+
+    let mut parts = text(lines[0])?.splitn(3, ' ');
+    let start_line = match (parts.next(), parts.next(), parts.next()) {
+        (Some(a), Some(b), Some(c)) if !a.is_empty() && !b.is_empty() =>
+            (a.to_string(), b.to_string(), c.to_string()),
+        // a response may have an empty reason phrase
+        (Some(a), Some(b), None) if a.starts_with("HTTP/") =>
+            (a.to_string(), b.to_string(), String::new()),
+        _ => return Err(invalid_data(MALFORMED_START_LINE))
+    };
+    let version_str = if start_line.0.starts_with("HTTP/") {
+        &start_line.0
+    } else {
+        &start_line.2
+    };
+    let version = match version_str.as_str() {
+        "HTTP/1.1" => Version::Http11,
+        "HTTP/1.0" => Version::Http10,
+        _ => return Err(invalid_data(UNSUPPORTED_VERSION))
+    };
+
+    let mut headers = Vec::with_capacity(lines.len() - 1);
+    for line in &lines[1..] {
+        let line = text(*line)?;
+        // obsolete line folding is rejected (RFC 9112, 5.2)
+        if line.starts_with(' ') || line.starts_with('\t') {
+            return Err(invalid_data(MALFORMED_HEADER));
+        }
+        match line.split_once(':') {
+            // a name with spaces before the colon isn't a token (RFC 9112, 5.1)
+            Some((name, value)) if is_token(name) => {
+                headers.push((name.to_string(), value.trim().to_string()))
+            },
+            _ => return Err(invalid_data(MALFORMED_HEADER))
+        }
+    }
+
+    Ok(Some(Head { start_line, version, headers, raw }))
+}
+
+/// Copies a message body from reader to writer, respecting its framing.
+/// # Arguments
+///
+/// * `reader` - the buffered reader from which to read the body.
+/// * `writer` - the writer on which to forward the body.
+/// * `length` - how the end of the body is found.
+///
+/// # Return
+///
+/// * The number of bytes copied.
+pub async fn copy_body<R, W>(reader: &mut R, writer: &mut W, length: BodyLength) -> io::Result<u64>
+where R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin {
+    match length {
+        BodyLength::Empty => Ok(0),
+        BodyLength::Fixed(n) => copy_exact(reader, writer, n).await,
+        BodyLength::UntilClose => tokio::io::copy_buf(reader, writer).await,
+        BodyLength::Chunked => copy_chunked(reader, writer).await
+    }
+}
+
+/// Reads a whole message body in memory, decoding the chunked transfer
+/// coding. Used for small bodies only, e.g. the health checks responses.
+/// # Arguments
+///
+/// * `reader` - the buffered reader from which to read the body.
+/// * `length` - how the end of the body is found.
+/// * `limit` - max number of bytes to read, the rest is ignored.
+///
+/// # Return
+///
+/// * The decoded body, truncated at `limit` bytes.
+pub async fn read_body<R>(reader: &mut R, length: BodyLength, limit: u64) -> io::Result<Vec<u8>>
+where R: AsyncBufRead + Unpin {
+    let mut body = Vec::new();
+    match length {
+        BodyLength::Empty => (),
+        BodyLength::Fixed(n) => {
+            (&mut *reader).take(n.min(limit)).read_to_end(&mut body).await?;
+        },
+        BodyLength::UntilClose => {
+            (&mut *reader).take(limit).read_to_end(&mut body).await?;
+        },
+        BodyLength::Chunked => {
+            let mut line = Vec::with_capacity(32);
+            loop {
+                read_chunk_line(reader, &mut line).await?;
+                let size = chunk_size(&line)?;
+                let remaining = limit - body.len() as u64;
+                if size == 0 || remaining == 0 {
+                    break;
+                }
+                (&mut *reader).take(size.min(remaining)).read_to_end(&mut body).await?;
+                if size > remaining {
+                    break;
+                }
+                read_chunk_line(reader, &mut line).await?;
+            }
+        }
+    }
+    Ok(body)
+}
+
+/// Copies exactly n bytes, fails if the reader ends before.
+async fn copy_exact<R, W>(reader: &mut R, writer: &mut W, n: u64) -> io::Result<u64>
+where R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin {
+    let copied = tokio::io::copy_buf(&mut (&mut *reader).take(n), writer).await?;
+    if copied < n {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, UNEXPECTED_EOF));
+    }
+    Ok(copied)
+}
+
+/// Copies a chunked body, chunk extensions and trailers included.
+async fn copy_chunked<R, W>(reader: &mut R, writer: &mut W) -> io::Result<u64>
+where R: AsyncBufRead + Unpin, W: AsyncWrite + Unpin {
+    let mut total = 0;
+    let mut line = Vec::with_capacity(32);
+    loop {
+        read_chunk_line(reader, &mut line).await?;
+        // checked before forwarding the line
+        let size = chunk_size(&line)?;
+        writer.write_all(&line).await?;
+        total += line.len() as u64;
+
+        if size == 0 {
+            // trailer section, ends with an empty line
+            loop {
+                read_chunk_line(reader, &mut line).await?;
+                writer.write_all(&line).await?;
+                total += line.len() as u64;
+                if trim_line_end(&line, 0) == 0 {
+                    return Ok(total);
+                }
+            }
+        }
+
+        total += copy_exact(reader, writer, size).await?;
+        read_chunk_line(reader, &mut line).await?;
+        if trim_line_end(&line, 0) != 0 {
+            return Err(invalid_data(MALFORMED_CHUNK));
+        }
+        writer.write_all(&line).await?;
+        total += line.len() as u64;
+    }
+}
+
+/// Return the size of the chunk of a chunk line, without the extensions.
+/// Only hex digits are accepted (e.g. no "+" or spaces), so the size
+/// can't be read differently by the server that receives the line.
+fn chunk_size(line: &[u8]) -> io::Result<u64> {
+    let size = line[..trim_line_end(line, 0)].split(|b| *b == b';').next().unwrap_or_default();
+    if size.is_empty() || !size.iter().all(u8::is_ascii_hexdigit) {
+        return Err(invalid_data(MALFORMED_CHUNK));
+    }
+    // hex digits are ascii
+    let size = std::str::from_utf8(size).map_err(|_| invalid_data(MALFORMED_CHUNK))?;
+    u64::from_str_radix(size, 16).map_err(|_| invalid_data(MALFORMED_CHUNK))
+}
+
+/// Parse a decimal number of one or more digits, without sign
+fn parse_digits(value: &str) -> Option<u64> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// Return true if the header name is a token (RFC 9110, 5.6.2)
+fn is_token(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Reads a single line of a chunked body in `line`.
+async fn read_chunk_line<R>(reader: &mut R, line: &mut Vec<u8>) -> io::Result<()>
+where R: AsyncBufRead + Unpin {
+    line.clear();
+    (&mut *reader).take(MAX_CHUNK_LINE_SIZE).read_until(b'\n', line).await?;
+    match line.last() {
+        Some(b'\n') => Ok(()),
+        Some(_) if line.len() as u64 == MAX_CHUNK_LINE_SIZE => Err(invalid_data(MALFORMED_CHUNK)),
+        _ => Err(io::Error::new(io::ErrorKind::UnexpectedEof, UNEXPECTED_EOF))
+    }
+}
+
+/// Return the index of the end of the line starting at `start`, without "\r\n"
+fn trim_line_end(buf: &[u8], start: usize) -> usize {
+    let mut end = buf.len();
+    if end > start && buf[end - 1] == b'\n' {
+        end -= 1;
+    }
+    if end > start && buf[end - 1] == b'\r' {
+        end -= 1;
+    }
+    end
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A custom body of the error responses, loaded from a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorPage {
+    pub content_type: String,
+    pub body: Vec<u8>
+}
+
+impl ErrorPage {
+    /// Load the page from a file, the content type is
+    /// chosen by the file extension.
+    /// # Arguments
+    ///
+    /// * `path` - the path of the file.
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let body = std::fs::read(path)?;
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        let content_type = match extension.as_deref() {
+            Some("html") | Some("htm") => "text/html; charset=utf-8",
+            Some("json") => "application/json",
+            _ => "text/plain; charset=utf-8"
+        };
+        Ok(ErrorPage { content_type: content_type.to_string(), body })
+    }
+}
+
+/// The custom error pages by status code,
+/// the codes without a page get a short plain text body
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorPages {
+    pages: HashMap<u16, ErrorPage>
+}
+
+impl ErrorPages {
+    /// Set the page of a status code
+    pub fn insert(&mut self, status: u16, page: ErrorPage) {
+        self.pages.insert(status, page);
+    }
+
+    /// Return the page of a status code, if any
+    pub fn get(&self, status: u16) -> Option<&ErrorPage> {
+        self.pages.get(&status)
+    }
+}
+
+/// Return the reason phrase of the status codes sent by the balancer
+/// and by the admin API
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Error"
+    }
+}
+
+/// Writes an error response, used when the request can't be forwarded
+/// or the server didn't answer. The connection must be closed after it.
+/// # Arguments
+///
+/// * `writer` - the writer on which to send the response.
+/// * `status` - the status code, e.g. 502.
+/// * `pages` - the custom error pages.
+/// * `with_body` - false if the response has no body, e.g. for a HEAD request.
+pub async fn write_error_response<W>(
+    writer: &mut W,
+    status: u16,
+    pages: &ErrorPages,
+    with_body: bool
+) -> io::Result<()>
+where W: AsyncWrite + Unpin {
+    let reason = reason_phrase(status);
+    let default_body;
+    let (content_type, body) = match pages.get(status) {
+        Some(page) => (page.content_type.as_str(), page.body.as_slice()),
+        None => {
+            default_body = format!("{} {}\n", status, reason);
+            ("text/plain; charset=utf-8", default_body.as_bytes())
+        }
+    };
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, reason, content_type, body.len()
+    );
+    writer.write_all(head.as_bytes()).await?;
+    if with_body {
+        writer.write_all(body).await?;
+    }
+    writer.flush().await
+}
+
+/// Pumps the bytes in both directions until both sides are closed.
+/// Used after a successful protocol upgrade (e.g. WebSocket).
+pub async fn tunnel<CR, CW, BR, BW>(
+    client_reader: &mut CR,
+    client_writer: &mut CW,
+    backend_reader: &mut BR,
+    backend_writer: &mut BW
+) -> io::Result<(u64, u64)>
+where
+    CR: AsyncRead + Unpin, CW: AsyncWrite + Unpin,
+    BR: AsyncRead + Unpin, BW: AsyncWrite + Unpin
+{
+    let to_backend = async {
+        let n = tokio::io::copy(client_reader, backend_writer).await?;
+        backend_writer.shutdown().await?;
+        Ok::<u64, io::Error>(n)
+    };
+    let to_client = async {
+        let n = tokio::io::copy(backend_reader, client_writer).await?;
+        client_writer.shutdown().await?;
+        Ok::<u64, io::Error>(n)
+    };
+    tokio::try_join!(to_backend, to_client)
+}
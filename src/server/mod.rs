@@ -1,2 +1,10 @@
+pub mod admin;
 pub mod app;
-pub mod socket_address;
\ No newline at end of file
+pub mod backend_state;
+pub mod dns;
+pub mod health_check;
+pub mod http;
+pub mod outlier_detection;
+pub mod shutdown;
+pub mod socket_address;
+pub mod timeouts;
\ No newline at end of file
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+// Default values of the outlier detection configuration
+pub const DEFAULT_CONSECUTIVE_FAILURES: usize = 5;
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+pub const DEFAULT_BASE_EJECTION_TIME: Duration = Duration::from_secs(30);
+pub const DEFAULT_MAX_EJECTION_TIME: Duration = Duration::from_secs(300);
+pub const DEFAULT_MAX_EJECTION_PERCENT: usize = 50;
+
+
+/// Configuration of the passive outlier detection.
+/// The proxy reports the result of every connection (or request) and
+/// a server with too many consecutive failures is ejected from the pool
+/// for a time that doubles at every ejection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlierDetectionConfig {
+    /// consecutive failures needed to eject a server
+    pub consecutive_failures: usize,
+    /// max time between the first and the last of the consecutive failures
+    pub window: Duration,
+    /// duration of the first ejection, doubled at every next ejection
+    pub base_ejection_time: Duration,
+    /// max duration of an ejection, after this time without ejections
+    /// the duration goes back to the base one
+    pub max_ejection_time: Duration,
+    /// max percentage of the servers ejected at the same time
+    pub max_ejection_percent: usize
+}
+
+impl OutlierDetectionConfig {
+    /// Return the duration of an ejection.
+    /// # Arguments
+    ///
+    /// * `previous_ejections` - number of the previous ejections of the server
+    pub fn ejection_time(&self, previous_ejections: u32) -> Duration {
+        self.base_ejection_time
+            .saturating_mul(2u32.saturating_pow(previous_ejections))
+            .min(self.max_ejection_time)
+    }
+
+    /// Return true if a new server can be ejected.
+    /// # Arguments
+    ///
+    /// * `ejected` - number of the servers already ejected
+    /// * `total` - number of the servers
+    pub fn can_eject(&self, ejected: usize, total: usize) -> bool {
+        (ejected + 1) * 100 <= total * self.max_ejection_percent
+    }
+}
+
+impl Default for OutlierDetectionConfig {
+    fn default() -> Self {
+        OutlierDetectionConfig {
+            consecutive_failures: DEFAULT_CONSECUTIVE_FAILURES,
+            window: DEFAULT_WINDOW,
+            base_ejection_time: DEFAULT_BASE_EJECTION_TIME,
+            max_ejection_time: DEFAULT_MAX_EJECTION_TIME,
+            max_ejection_percent: DEFAULT_MAX_EJECTION_PERCENT
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    future::Future,
+    sync::{Arc, atomic::{AtomicUsize, Ordering}},
+    time::Duration
+};
+use tokio::{sync::{watch, Notify}, time};
+
+// Default max time for draining the connections on shutdown
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+
+/// How the active connections ended during a shutdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrainReport {
+    /// connections that finished before the drain timeout
+    pub drained: usize,
+    /// connections closed when the drain timeout expired
+    pub aborted: usize
+}
+
+
+/// The active connections of the server, used for the graceful shutdown
+#[derive(Debug)]
+pub struct Connections {
+    active: AtomicUsize,
+    /// notified when the last active connection ends
+    finished: Notify,
+    /// true when the shutdown started, no new requests should be started
+    draining: watch::Sender<bool>,
+    /// true when the drain timeout expired, every connection must end
+    aborting: watch::Sender<bool>
+}
+
+impl Connections {
+    pub fn new() -> Self {
+        Connections {
+            active: AtomicUsize::new(0),
+            finished: Notify::new(),
+            draining: watch::channel(false).0,
+            aborting: watch::channel(false).0
+        }
+    }
+
+    /// Return the number of active connections
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Track a new connection, until the returned guard is dropped.
+    /// Must be called when the connection is accepted, so it's
+    /// counted even if its task didn't start yet.
+    pub fn track(self: &Arc<Self>) -> ConnectionGuard {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard { connections: Arc::clone(self) }
+    }
+
+    /// Return true if the shutdown started
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    /// Wait until the shutdown starts
+    pub async fn draining(&self) {
+        wait_for_true(self.draining.subscribe()).await
+    }
+
+    /// Wait the end of the active connections up to the timeout,
+    /// then abort the remaining ones.
+    /// # Arguments
+    ///
+    /// * `timeout` - max time for the connections to end by themselves
+    ///
+    /// # Return
+    ///
+    /// * How many connections were drained and aborted
+    pub async fn drain(&self, timeout: Duration) -> DrainReport {
+        let total = self.active();
+        let _ = self.draining.send(true);
+
+        let remaining = match time::timeout(timeout, self.wait_finished()).await {
+            Ok(()) => 0,
+            Err(_) => {
+                let remaining = self.active();
+                let _ = self.aborting.send(true);
+                self.wait_finished().await;
+                remaining
+            }
+        };
+        DrainReport {
+            drained: total.saturating_sub(remaining),
+            aborted: remaining
+        }
+    }
+
+    /// Wait until there are no active connections
+    async fn wait_finished(&self) {
+        while self.active() > 0 {
+            self.finished.notified().await;
+        }
+    }
+}
+
+impl Default for Connections {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// An active connection, it ends when dropped
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    connections: Arc<Connections>
+}
+
+impl ConnectionGuard {
+    /// Run the connection until it ends or it's aborted by the shutdown
+    pub async fn run<F>(self, connection: F)
+    where F: Future<Output = ()> {
+        tokio::select! {
+            _ = connection => (),
+            _ = wait_for_true(self.connections.aborting.subscribe()) => ()
+        }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if self.connections.active.fetch_sub(1, Ordering::Relaxed) == 1 {
+            // the permit is stored if the drain isn't waiting yet
+            self.connections.finished.notify_one();
+        }
+    }
+}
+
+/// Wait until the value of the channel is true
+async fn wait_for_true(mut receiver: watch::Receiver<bool>) {
+    while !*receiver.borrow_and_update() {
+        if receiver.changed().await.is_err() {
+            // the sender is dropped, the value can't change anymore
+            std::future::pending::<()>().await;
+        }
+    }
+}
@@ -1,73 +1,246 @@
-use std::fmt::{Display, self};
-use regex::Regex;
+use std::{
+    fmt::{Display, self},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
+    str::FromStr
+};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-pub static IPV4_REGEX: &str = r"^(\d{1,3}\.){3}\d{1,3}$";
 pub static IPV4_ERROR: &str = "Invalid IPv4 address";
+pub static IPV6_ERROR: &str = "Invalid IPv6 address";
+pub static ZONE_ID_ERROR: &str = "A zone ID is allowed only on a link-local IPv6 address";
 pub static PORT_NUMBER_ERROR: &str = "Invalid Port Number";
+pub static UNKNOWN_ZONE_ID: &str = "Unknown zone ID";
+pub static MISSING_PORT: &str = "The port number is missing";
+pub static MISSING_BRACKETS: &str = "An IPv6 address must be in brackets, e.g. [::1]:8080";
 
 
-/// Stores a socket address
-/// # Arguments
-///
-/// * `0` - IPv4 address
-/// * `1` - Port number
-#[derive(Debug)]
-pub struct SocketAddress(String, String);
+/// Why a socket address can't be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketAddressError {
+    /// not 4 decimal bytes without leading zeros
+    InvalidIpv4,
+    InvalidIpv6,
+    /// a zone ID on an IPv6 address that isn't link-local
+    ZoneId,
+    /// the zone ID isn't the name or the index of an interface
+    UnknownZoneId,
+    /// not a number from 1 to 65535
+    InvalidPort,
+    /// an address without ":PORT"
+    MissingPort,
+    /// an IPv6 address followed by the port without brackets
+    MissingBrackets
+}
+
+impl SocketAddressError {
+    /// Return the message of the error
+    pub fn reason(&self) -> &'static str {
+        match self {
+            SocketAddressError::InvalidIpv4 => IPV4_ERROR,
+            SocketAddressError::InvalidIpv6 => IPV6_ERROR,
+            SocketAddressError::ZoneId => ZONE_ID_ERROR,
+            SocketAddressError::UnknownZoneId => UNKNOWN_ZONE_ID,
+            SocketAddressError::InvalidPort => PORT_NUMBER_ERROR,
+            SocketAddressError::MissingPort => MISSING_PORT,
+            SocketAddressError::MissingBrackets => MISSING_BRACKETS
+        }
+    }
+}
+
+impl Display for SocketAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.reason())
+    }
+}
+
+impl std::error::Error for SocketAddressError {}
+
+
+/// Stores a socket address, IPv4 or IPv6.
+/// The zone ID of a link-local IPv6 address (e.g. `fe80::1%eth0`)
+/// is stored as the index of the interface.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SocketAddress {
+    address: SocketAddr
+}
 
 impl SocketAddress {
     /// Create and return a new instance of SocketAddress tuple struct
     /// # Arguments
     ///
-    /// * `ipv4` - ipv4 address
+    /// * `ipv4` - ipv4 address, or ipv6 address without brackets
     /// * `port_number` - port number
     pub fn new(ipv4: String, port_number: String) -> Result<Self, &'static str> {
-        let ipv4_reg = Regex::new(IPV4_REGEX).unwrap();
+        Self::from_parts(&ipv4, &port_number).map_err(|error| error.reason())
+    }
+
+    /// Parse the ip address and the port number, the ip is checked first.
+    /// # Arguments
+    ///
+    /// * `ip` - ipv4 address, or ipv6 address without brackets
+    ///   with the optional zone ID
+    /// * `port_number` - port number, from 1 to 65535
+    pub fn from_parts(ip: &str, port_number: &str) -> Result<Self, SocketAddressError> {
+        let address = if ip.contains(':') {
+            let (address, scope_id) = Self::parse_ipv6(ip)?;
+            SocketAddr::V6(SocketAddrV6::new(address, Self::parse_port(port_number)?, 0, scope_id))
+        } else {
+            // the std parser refuses the leading zeros
+            let address: Ipv4Addr = ip.parse().map_err(|_| SocketAddressError::InvalidIpv4)?;
+            SocketAddr::new(IpAddr::V4(address), Self::parse_port(port_number)?)
+        };
+        Ok(SocketAddress::from(address))
+    }
 
-        // check IPv4
-        if !ipv4_reg.is_match(&ipv4) {
-            return Err(IPV4_ERROR);
+    /// Parse a port number, only digits from 1 to 65535
+    pub fn parse_port(port_number: &str) -> Result<u16, SocketAddressError> {
+        if port_number.is_empty() || !port_number.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(SocketAddressError::InvalidPort);
         }
-        let bytes_splitted = ipv4.split(".");
-        for byte in bytes_splitted {
-            let byte_parsed: Result<i32, _> = byte.parse();
-            match byte_parsed {
-                Err(_) => return Err(IPV4_ERROR),
-                Ok(n) => if n > 255 {return Err(IPV4_ERROR);}
-            };
+        match port_number.parse() {
+            Ok(0) | Err(_) => Err(SocketAddressError::InvalidPort),
+            Ok(port) => Ok(port)
         }
+    }
 
-        // check port number
-        let parsed_port_number: Result<i32, _> = port_number.parse();
-        match parsed_port_number {
-            Err(_) => return Err(PORT_NUMBER_ERROR),
-            Ok(n) => if n >= (1 << 16) {return Err(PORT_NUMBER_ERROR);}
+    /// Parse an ipv6 address with an optional zone ID.
+    /// # Return
+    ///
+    /// * The address and the scope ID, 0 without zone ID
+    fn parse_ipv6(ipv6: &str) -> Result<(Ipv6Addr, u32), SocketAddressError> {
+        let (address, zone) = match ipv6.split_once('%') {
+            Some((address, zone)) => (address, Some(zone)),
+            None => (ipv6, None)
         };
+        let address: Ipv6Addr = address.parse().map_err(|_| SocketAddressError::InvalidIpv6)?;
+        match zone {
+            None => Ok((address, 0)),
+            Some(zone) if zone.is_empty() || !zone.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) => {
+                Err(SocketAddressError::InvalidIpv6)
+            },
+            Some(_) if !is_link_local(&address) => Err(SocketAddressError::ZoneId),
+            Some(zone) => Ok((address, scope_id(zone)?))
+        }
+    }
 
-        Ok(SocketAddress(ipv4, port_number))
+    /// Return true if the address is an IPv6 address
+    pub fn is_ipv6(&self) -> bool {
+        self.address.is_ipv6()
     }
- 
+
+    /// Return the ip address without the zone ID
+    pub fn ip(&self) -> IpAddr {
+        self.address.ip()
+    }
+
+    /// Return the port number
+    pub fn port(&self) -> u16 {
+        self.address.port()
+    }
+
+    /// Return the std socket address, used for connecting and binding
+    pub fn to_socket_addr(&self) -> SocketAddr {
+        self.address
+    }
+
     /// Return port number
-    pub fn get_port_number(&self) -> &String {
-        &self.1
+    pub fn get_port_number(&self) -> String {
+        self.address.port().to_string()
     }
 
-    /// Return ipv4 address
-    pub fn get_ipv4(&self) -> &String {
-        &self.0
+    /// Return ipv4 (or ipv6) address, with the index
+    /// of the interface as zone ID if any
+    pub fn get_ipv4(&self) -> String {
+        match self.address {
+            SocketAddr::V6(address) if address.scope_id() != 0 => {
+                format!("{}%{}", address.ip(), address.scope_id())
+            },
+            address => address.ip().to_string()
+        }
     }
 
-    /// Return the complete socket address with format IPv4:PORT
+    /// Return the complete socket address with format IPv4:PORT,
+    /// or [IPv6]:PORT for an IPv6 address
     pub fn get(&self) -> String {
-        let mut s = String::with_capacity(20);
-        s.push_str(&self.0);
-        s.push(':');
-        s.push_str(&self.1);
-        return s;
+        self.address.to_string()
+    }
+}
+
+/// Return true for the unicast link-local addresses, fe80::/10
+fn is_link_local(address: &Ipv6Addr) -> bool {
+    address.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// Return the index of the interface of a zone ID,
+/// that can be the index itself or the name of the interface
+fn scope_id(zone: &str) -> Result<u32, SocketAddressError> {
+    if let Ok(index) = zone.parse() {
+        return Ok(index);
+    }
+    #[cfg(unix)]
+    if let Ok(name) = std::ffi::CString::new(zone) {
+        // SAFETY: name is a valid nul terminated string
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index != 0 {
+            return Ok(index);
+        }
     }
+    Err(SocketAddressError::UnknownZoneId)
 }
 
 impl Display for SocketAddress {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.get())
+        Display::fmt(&self.address, f)
+    }
+}
+
+/// Parse an address with format IPv4:PORT or [IPv6]:PORT,
+/// the IPv6 address can have a zone ID
+impl FromStr for SocketAddress {
+    type Err = SocketAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, port) = s.rsplit_once(':').ok_or(SocketAddressError::MissingPort)?;
+        let ip = match ip.strip_prefix('[') {
+            Some(ipv6) => ipv6.strip_suffix(']').ok_or(SocketAddressError::InvalidIpv6)?,
+            // the brackets are required, otherwise the port is ambiguous
+            None if ip.contains(':') => return Err(SocketAddressError::MissingBrackets),
+            None => ip
+        };
+        Self::from_parts(ip, port)
+    }
+}
+
+impl TryFrom<&str> for SocketAddress {
+    type Error = SocketAddressError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<SocketAddr> for SocketAddress {
+    fn from(address: SocketAddr) -> Self {
+        SocketAddress { address }
+    }
+}
+
+impl From<SocketAddress> for SocketAddr {
+    fn from(address: SocketAddress) -> Self {
+        address.address
     }
-}
\ No newline at end of file
+}
+
+/// Serialized as a string with format IPv4:PORT or [IPv6]:PORT
+impl Serialize for SocketAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SocketAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
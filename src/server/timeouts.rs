@@ -0,0 +1,426 @@
+use std::{
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{atomic::{AtomicU64, AtomicUsize, Ordering}, Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{self, Instant, Sleep}
+};
+
+
+/// The kinds of timeout, each one is logged and counted separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutKind {
+    /// the connection to the server wasn't opened in time
+    Connect,
+    /// the client didn't send its first byte in time
+    ClientFirstByte,
+    /// the server didn't send its first byte in time
+    BackendFirstByte,
+    /// the client didn't send or receive bytes for too long
+    ClientIdle,
+    /// the server didn't send or receive bytes for too long
+    BackendIdle,
+    /// the connection lasted more than its max lifetime
+    Lifetime
+}
+
+// Number of timeouts of each kind, in the order of the enum
+static TIMEOUTS: [AtomicUsize; 6] = [
+    AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0),
+    AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)
+];
+
+impl TimeoutKind {
+    /// Every kind of timeout, in the order of the enum
+    pub const ALL: [TimeoutKind; 6] = [
+        TimeoutKind::Connect, TimeoutKind::ClientFirstByte, TimeoutKind::BackendFirstByte,
+        TimeoutKind::ClientIdle, TimeoutKind::BackendIdle, TimeoutKind::Lifetime
+    ];
+
+    /// Return the key of the timeout in "Timeouts"
+    pub fn key(&self) -> &'static str {
+        match self {
+            TimeoutKind::Connect => "connect",
+            TimeoutKind::ClientFirstByte => "client_first_byte",
+            TimeoutKind::BackendFirstByte => "backend_first_byte",
+            TimeoutKind::ClientIdle => "client_idle",
+            TimeoutKind::BackendIdle => "backend_idle",
+            TimeoutKind::Lifetime => "lifetime"
+        }
+    }
+
+    /// Return the name used in the logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimeoutKind::Connect => "connect timeout",
+            TimeoutKind::ClientFirstByte => "client first byte timeout",
+            TimeoutKind::BackendFirstByte => "backend first byte timeout",
+            TimeoutKind::ClientIdle => "client idle timeout",
+            TimeoutKind::BackendIdle => "backend idle timeout",
+            TimeoutKind::Lifetime => "lifetime timeout"
+        }
+    }
+
+    /// Return true if the timeout is caused by the server,
+    /// so it's reported to the outlier detection
+    pub fn is_backend(&self) -> bool {
+        matches!(self, TimeoutKind::Connect | TimeoutKind::BackendFirstByte | TimeoutKind::BackendIdle)
+    }
+
+    /// Return the number of timeouts of this kind since the start
+    pub fn count(&self) -> usize {
+        TIMEOUTS[*self as usize].load(Ordering::Relaxed)
+    }
+
+    /// Count a timeout of this kind and return it as an io error
+    pub fn error(self) -> io::Error {
+        TIMEOUTS[self as usize].fetch_add(1, Ordering::Relaxed);
+        io::Error::new(io::ErrorKind::TimedOut, self)
+    }
+
+    /// Return the kind of timeout of an io error, if any
+    pub fn of(error: &io::Error) -> Option<TimeoutKind> {
+        error.get_ref()?.downcast_ref::<TimeoutKind>().copied()
+    }
+}
+
+impl fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::error::Error for TimeoutKind {}
+
+
+/// Configuration of the timeouts, None disables a timeout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeoutConfig {
+    /// max time for opening the connection to a server
+    pub connect: Option<Duration>,
+    /// max time waiting for the first byte of the client
+    pub client_first_byte: Option<Duration>,
+    /// max time waiting for the first byte of the server
+    pub backend_first_byte: Option<Duration>,
+    /// max time waiting for a read or a write on the client side
+    pub client_idle: Option<Duration>,
+    /// max time waiting for a read or a write on the server side
+    pub backend_idle: Option<Duration>,
+    /// max duration of a client connection
+    pub lifetime: Option<Duration>
+}
+
+/// The two sides of a proxied connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Client,
+    Backend
+}
+
+impl Side {
+    /// Return the side that caused an io error of a `TimedStream`,
+    /// None if the error doesn't come from a `TimedStream`
+    pub fn of(error: &io::Error) -> Option<Side> {
+        if let Some(kind) = TimeoutKind::of(error) {
+            return Some(if kind.is_backend() { Side::Backend } else { Side::Client });
+        }
+        error.get_ref()?.downcast_ref::<SideError>().map(|error| error.side)
+    }
+}
+
+/// An io error of a socket, tagged with the side of the socket
+#[derive(Debug)]
+struct SideError {
+    side: Side,
+    error: io::Error
+}
+
+impl SideError {
+    /// Return the error tagged with the side, with the same kind
+    fn tag(side: Side, error: io::Error) -> io::Error {
+        io::Error::new(error.kind(), SideError { side, error })
+    }
+}
+
+impl fmt::Display for SideError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for SideError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+
+/// Runs the future with a timeout.
+/// # Arguments
+///
+/// * `timeout` - the max duration, None for no timeout
+/// * `kind` - the kind of the error returned if the time is over
+/// * `future` - the future to run
+pub async fn with_timeout<F, T>(timeout: Option<Duration>, kind: TimeoutKind, future: F) -> io::Result<T>
+where F: Future<Output = io::Result<T>> {
+    match timeout {
+        None => future.await,
+        Some(timeout) => time::timeout(timeout, future).await
+            .unwrap_or_else(|_| Err(kind.error()))
+    }
+}
+
+
+/// The activity of a socket, shared by its halves
+#[derive(Debug)]
+struct Activity {
+    /// when the socket was wrapped, the origin of the other times
+    created: Instant,
+    /// nanoseconds from created to the last read or write
+    last: AtomicU64,
+    /// nanoseconds from created to the start of the wait
+    /// for the first byte, NOT_STARTED if it didn't start
+    first_byte_from: AtomicU64,
+    /// the read waiting for the start of the first byte timeout
+    reader: Mutex<Option<Waker>>
+}
+
+// Value of first_byte_from before the wait for the first byte starts
+const NOT_STARTED: u64 = u64::MAX;
+
+impl Activity {
+    fn new() -> Arc<Self> {
+        Arc::new(Activity {
+            created: Instant::now(),
+            last: AtomicU64::new(0),
+            first_byte_from: AtomicU64::new(0),
+            reader: Mutex::new(None)
+        })
+    }
+
+    /// Return the nanoseconds from created to now
+    fn now(&self) -> u64 {
+        self.created.elapsed().as_nanos() as u64
+    }
+
+    /// Record a read or a write
+    fn touch(&self) {
+        self.last.fetch_max(self.now(), Ordering::Relaxed);
+    }
+
+    /// Return the deadline of the idle timeout
+    fn idle_deadline(&self, idle: Duration) -> Instant {
+        self.created + Duration::from_nanos(self.last.load(Ordering::Relaxed)) + idle
+    }
+
+    /// Return the deadline of the first byte timeout, None if the wait didn't start
+    fn first_byte_deadline(&self, first_byte: Duration) -> Option<Instant> {
+        match self.first_byte_from.load(Ordering::Relaxed) {
+            NOT_STARTED => None,
+            from => Some(self.created + Duration::from_nanos(from) + first_byte)
+        }
+    }
+}
+
+/// A socket (or half of it) with first byte and idle timeouts.
+/// A read waiting for more than the timeout fails with the first byte
+/// timeout until the first byte is read, then with the idle timeout.
+/// A write waiting for more than the idle timeout fails with it,
+/// because the peer isn't reading.
+/// The idle time is the time since the last read or write of any half
+/// of the socket, so a side streaming in one direction isn't idle.
+/// The errors of the socket are tagged with its side, see `Side::of`.
+#[derive(Debug)]
+pub struct TimedStream<S> {
+    inner: S,
+    side: Side,
+    first_byte: Option<(Duration, TimeoutKind)>,
+    idle: Option<(Duration, TimeoutKind)>,
+    first_byte_read: bool,
+    activity: Arc<Activity>,
+    read_deadline: Option<Pin<Box<Sleep>>>,
+    write_deadline: Option<Pin<Box<Sleep>>>
+}
+
+impl<S> TimedStream<S> {
+    /// Wrap a socket with the timeouts of its side.
+    /// # Arguments
+    ///
+    /// * `inner` - the socket to wrap
+    /// * `side` - the side of the socket, the client or the server
+    /// * `config` - the timeouts configuration
+    pub fn new(inner: S, side: Side, config: &TimeoutConfig) -> Self {
+        Self::with_activity(inner, side, config, Activity::new())
+    }
+
+    /// Wrap the two halves of a socket with the timeouts of its side,
+    /// a read or a write on a half resets the idle time of both.
+    /// # Arguments
+    ///
+    /// * `read_half` - the read half of the socket
+    /// * `write_half` - the write half of the socket
+    /// * `side` - the side of the socket, the client or the server
+    /// * `config` - the timeouts configuration
+    pub fn halves<W>(read_half: S, write_half: W, side: Side, config: &TimeoutConfig) -> (Self, TimedStream<W>) {
+        let activity = Activity::new();
+        (
+            Self::with_activity(read_half, side, config, activity.clone()),
+            TimedStream::with_activity(write_half, side, config, activity)
+        )
+    }
+
+    fn with_activity(inner: S, side: Side, config: &TimeoutConfig, activity: Arc<Activity>) -> Self {
+        let (first_byte, idle) = match side {
+            Side::Client => (
+                config.client_first_byte.map(|t| (t, TimeoutKind::ClientFirstByte)),
+                config.client_idle.map(|t| (t, TimeoutKind::ClientIdle))
+            ),
+            Side::Backend => (
+                config.backend_first_byte.map(|t| (t, TimeoutKind::BackendFirstByte)),
+                config.backend_idle.map(|t| (t, TimeoutKind::BackendIdle))
+            )
+        };
+        TimedStream {
+            inner,
+            side,
+            first_byte,
+            idle,
+            first_byte_read: false,
+            activity,
+            read_deadline: None,
+            write_deadline: None
+        }
+    }
+
+    /// Wait for the first byte only after `start_first_byte`,
+    /// e.g. after the request is sent. Until then the reads have
+    /// the idle timeout.
+    pub fn defer_first_byte(&self) {
+        self.activity.first_byte_from.store(NOT_STARTED, Ordering::Relaxed);
+    }
+
+    /// Start the wait for the first byte, deferred by `defer_first_byte`
+    pub fn start_first_byte(&self) {
+        let _ = self.activity.first_byte_from.compare_exchange(
+            NOT_STARTED, self.activity.now(), Ordering::Relaxed, Ordering::Relaxed
+        );
+        if let Some(reader) = self.activity.reader.lock().unwrap().take() {
+            reader.wake();
+        }
+    }
+
+    /// Return the deadline of a pending read and its kind of timeout
+    fn read_timeout(&self, cx: &mut Context<'_>) -> Option<(Instant, TimeoutKind)> {
+        if let (false, Some((first_byte, kind))) = (self.first_byte_read, self.first_byte) {
+            match self.activity.first_byte_deadline(first_byte) {
+                Some(deadline) => return Some((deadline, kind)),
+                // woken up when the wait starts
+                None => *self.activity.reader.lock().unwrap() = Some(cx.waker().clone())
+            }
+        }
+        self.write_timeout()
+    }
+
+    /// Return the deadline of a pending write and its kind of timeout
+    fn write_timeout(&self) -> Option<(Instant, TimeoutKind)> {
+        self.idle.map(|(idle, kind)| (self.activity.idle_deadline(idle), kind))
+    }
+
+    /// Tag an error of the socket with its side, record the activity otherwise
+    fn result<T>(&self, result: io::Result<T>) -> io::Result<T> {
+        match result {
+            Ok(value) => {
+                self.activity.touch();
+                Ok(value)
+            },
+            Err(error) => Err(SideError::tag(self.side, error))
+        }
+    }
+}
+
+/// Polls the deadline of a pending operation, creating it at the first call
+/// and moving it when the deadline changes (e.g. after the activity of the
+/// other half). Return the timeout error if the deadline is passed.
+fn poll_deadline(
+    sleep: &mut Option<Pin<Box<Sleep>>>,
+    timeout: Option<(Instant, TimeoutKind)>,
+    cx: &mut Context<'_>
+) -> Poll<io::Error> {
+    let (deadline, kind) = match timeout {
+        Some(timeout) => timeout,
+        None => return Poll::Pending
+    };
+    let pending = sleep.get_or_insert_with(|| Box::pin(time::sleep_until(deadline)));
+    if pending.deadline() != deadline {
+        pending.as_mut().reset(deadline);
+    }
+    match pending.as_mut().poll(cx) {
+        Poll::Ready(()) => {
+            *sleep = None;
+            Poll::Ready(kind.error())
+        },
+        Poll::Pending => Poll::Pending
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TimedStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let filled = buf.filled().len();
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                this.read_deadline = None;
+                if buf.filled().len() > filled {
+                    this.first_byte_read = true;
+                }
+                Poll::Ready(this.result(result))
+            },
+            Poll::Pending => {
+                let timeout = this.read_timeout(cx);
+                poll_deadline(&mut this.read_deadline, timeout, cx).map(Err)
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TimedStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.inner).poll_write(cx, buf) {
+            Poll::Ready(result) => {
+                this.write_deadline = None;
+                Poll::Ready(this.result(result))
+            },
+            Poll::Pending => {
+                let timeout = this.write_timeout();
+                poll_deadline(&mut this.write_deadline, timeout, cx).map(Err)
+            }
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.inner).poll_flush(cx) {
+            Poll::Ready(result) => {
+                this.write_deadline = None;
+                Poll::Ready(result.map_err(|error| SideError::tag(this.side, error)))
+            },
+            Poll::Pending => {
+                let timeout = this.write_timeout();
+                poll_deadline(&mut this.write_deadline, timeout, cx).map(Err)
+            }
+        }
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let side = self.side;
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+            .map_err(|error| SideError::tag(side, error))
+    }
+}
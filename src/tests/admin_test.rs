@@ -0,0 +1,197 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::{mpsc, oneshot}
+    };
+    use crate::{
+        balancers::ZERO_OR_NEGATIVE_SERVERS,
+        server::{
+            admin::*,
+            app::Server,
+            backend_state::BackendStates,
+            socket_address::{SocketAddress, MISSING_PORT}
+        },
+        tests::fixtures::*
+    };
+
+    fn socket_address(s: &str) -> SocketAddress {
+        s.parse().unwrap()
+    }
+
+    fn error(status: u16, message: &str) -> Result<AdminRequest, AdminResponse> {
+        Err(AdminResponse::error(status, message))
+    }
+
+    #[test]
+    fn requests_are_routed_by_method_and_path() {
+        assert_eq!(parse_request("GET", "/backends", b""), Ok(AdminRequest::List));
+        assert_eq!(parse_request("GET", "/backends/?pretty", b""), Ok(AdminRequest::List));
+        assert_eq!(
+            parse_request("POST", "/backends", br#"{"address": "10.0.0.1:80", "weight": 2}"#),
+            Ok(AdminRequest::Add { socket_address: socket_address("10.0.0.1:80"), weight: 2 })
+        );
+        assert_eq!(
+            parse_request("DELETE", "/backends/%5B::1%5D:80", b""),
+            Ok(AdminRequest::Remove(socket_address("[::1]:80")))
+        );
+        assert_eq!(
+            parse_request("PUT", "/backends/10.0.0.1:80/weight", br#"{"weight": 0}"#),
+            Ok(AdminRequest::Reweight { socket_address: socket_address("10.0.0.1:80"), weight: 0 })
+        );
+        assert_eq!(
+            parse_request("POST", "/backends/10.0.0.1:80/drain", b""),
+            Ok(AdminRequest::Drain(socket_address("10.0.0.1:80")))
+        );
+        assert_eq!(
+            parse_request("DELETE", "/backends/10.0.0.1:80/drain", b""),
+            Ok(AdminRequest::Undrain(socket_address("10.0.0.1:80")))
+        );
+    }
+
+    #[test]
+    fn invalid_requests_are_errors() {
+        assert_eq!(parse_request("GET", "/servers", b""), error(404, UNKNOWN_ENDPOINT));
+        assert_eq!(parse_request("PATCH", "/backends/10.0.0.1:80", b""), error(405, METHOD_NOT_ALLOWED));
+        assert_eq!(parse_request("POST", "/backends", b"weight=1"), error(400, INVALID_BODY));
+        assert_eq!(parse_request("POST", "/backends", br#"{"weight": 1}"#), error(400, MISSING_ADDRESS));
+        assert_eq!(
+            parse_request("POST", "/backends", br#"{"address": "10.0.0.1:80", "weight": -1}"#),
+            error(400, INVALID_WEIGHT)
+        );
+        assert_eq!(parse_request("DELETE", "/backends/10.0.0.1", b""), error(400, MISSING_PORT));
+        assert_eq!(parse_request("DELETE", "/backends/%5G", b""), error(400, INVALID_PATH));
+    }
+
+    #[test]
+    fn drained_servers_are_not_available() {
+        let server = socket_address("10.0.0.1:80");
+        let states = BackendStates::new(&[(server.clone(), 1)]);
+        states.get(&server).unwrap().set_drained(true);
+        assert!(!states.is_available(&server));
+
+        // the state is kept by the next pools
+        let next = BackendStates::new(&[(server.clone(), 1)]).with_previous(&states);
+        assert!(!next.is_available(&server));
+        next.get(&server).unwrap().set_drained(false);
+        assert!(states.is_available(&server));
+    }
+
+    #[tokio::test]
+    async fn servers_change_while_the_balancer_runs() {
+        let (first, second) = (backend("first").await, backend("second").await);
+        let (listen_port, admin_port) = (free_port().await, free_port().await);
+        let config = configure_json("admin", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Admin": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Servers": [{{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}]
+        }}"#, listen_port, admin_port, first)).unwrap();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let (_reload_sender, reloads) = mpsc::channel(1);
+        let mut server = Server::new(config);
+        let server = tokio::spawn(async move {
+            server.run(async { let _ = stopped.await; }, reloads).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let (first, second) = (format!("127.0.0.1:{first}"), format!("127.0.0.1:{second}"));
+        let added = format!(r#"{{ "address": "{second}", "weight": 1 }}"#);
+        let (status, body) = admin(admin_port, "POST", "/backends", &added).await;
+        assert_eq!(status, 201);
+        assert_eq!(body["address"], json!(second));
+        assert_eq!(admin(admin_port, "POST", "/backends", &added).await.0, 409);
+
+        let (status, body) = admin(admin_port, "POST", &format!("/backends/{first}/drain"), "").await;
+        assert_eq!((status, &body["drained"], &body["available"]), (200, &json!(true), &json!(false)));
+        for _ in 0..4 {
+            assert_eq!(request(listen_port).await, "second");
+        }
+
+        let (status, body) = admin(admin_port, "PUT", &format!("/backends/{second}/weight"), r#"{"weight": 5}"#).await;
+        assert_eq!((status, &body["weight"]), (200, &json!(5)));
+        let (_, body) = admin(admin_port, "GET", "/backends", "").await;
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        assert_eq!(body[0]["drained"], json!(true));
+
+        assert_eq!(admin(admin_port, "DELETE", &format!("/backends/{first}"), "").await.0, 200);
+        assert_eq!(admin(admin_port, "DELETE", &format!("/backends/{first}"), "").await.0, 404);
+        let (status, body) = admin(admin_port, "DELETE", &format!("/backends/{second}"), "").await;
+        assert_eq!(status, 409);
+        assert_eq!(body["error"], json!(format!("Servers: {}", ZERO_OR_NEGATIVE_SERVERS)));
+        assert_eq!(request(listen_port).await, "second");
+
+        stop.send(()).unwrap();
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    /// Accept the connections, write the name of the backend
+    /// and keep them open until the client closes, return the port
+    async fn holding_backend(name: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let _ = socket.write_all(name.as_bytes()).await;
+                    let _ = socket.read_to_end(&mut Vec::new()).await;
+                });
+            }
+        });
+        port
+    }
+
+    /// Open a connection and read the name of the backend, the connection is kept open
+    async fn hold(port: u16) -> (TcpStream, String) {
+        let mut socket = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut name = [0; 6];
+        socket.read_exact(&mut name).await.unwrap();
+        (socket, String::from_utf8(name.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn in_flight_connections_are_kept_across_a_change_of_the_servers() {
+        let (first, second) = (holding_backend("first_").await, holding_backend("second").await);
+        let (listen_port, admin_port) = (free_port().await, free_port().await);
+        let config = configure_json("admin_in_flight", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Admin": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Algorithm": "least_connections",
+            "Servers": [{{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}]
+        }}"#, listen_port, admin_port, first)).unwrap();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let (_reload_sender, reloads) = mpsc::channel(1);
+        let mut server = Server::new(config);
+        let server = tokio::spawn(async move {
+            server.run(async { let _ = stopped.await; }, reloads).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut held = Vec::new();
+        for _ in 0..2 {
+            held.push(hold(listen_port).await);
+        }
+        let added = format!(r#"{{ "address": "127.0.0.1:{second}", "weight": 1 }}"#);
+        assert_eq!(admin(admin_port, "POST", "/backends", &added).await.0, 201);
+
+        // the first server still has 2 connections, the new one takes the next 2
+        for _ in 0..2 {
+            let (socket, name) = hold(listen_port).await;
+            assert_eq!(name, "second");
+            held.push((socket, name));
+        }
+
+        // the connections opened before the change are released on the new balancer
+        held.retain(|(_, name)| name == "second");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(hold(listen_port).await.1, "first_");
+
+        stop.send(()).unwrap();
+        drop(held);
+        assert!(server.await.unwrap().is_ok());
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+    use crate::{
+        cli::*,
+        log::LogLevel,
+        balancers::Algorithm,
+        server::socket_address::*
+    };
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> Result<Cli, CliError> {
+        let env: HashMap<String, String> = env.iter()
+            .map(|(variable, value)| (variable.to_string(), value.to_string()))
+            .collect();
+        Cli::parse(args.iter().map(|arg| arg.to_string()), |variable| env.get(variable).cloned())
+    }
+
+    #[test]
+    fn defaults_without_arguments() {
+        let cli = parse(&[], &[]).unwrap();
+        assert_eq!(cli.command, Command::Run);
+        assert_eq!(cli.config, PathBuf::from(DEFAULT_CONFIG_PATH));
+        assert_eq!(cli.overrides, Overrides::default());
+        assert_eq!(cli.log_level, LogLevel::Info);
+    }
+
+    #[test]
+    fn options_are_parsed_with_separate_or_inline_values() {
+        let cli = parse(
+            &["--config", "/etc/lb.json", "--listen=0.0.0.0:80", "--algorithm", "maglev", "--log-level=debug"],
+            &[]
+        ).unwrap();
+        assert_eq!(cli.config, PathBuf::from("/etc/lb.json"));
+        assert_eq!(cli.overrides.listen, Some(SocketAddress::new(String::from("0.0.0.0"), String::from("80")).unwrap()));
+        assert_eq!(cli.overrides.algorithm, Some(Algorithm::Maglev));
+        assert_eq!(cli.log_level, LogLevel::Debug);
+    }
+
+    #[test]
+    fn ipv6_listen_needs_brackets() {
+        let cli = parse(&["--listen", "[::]:8080"], &[]).unwrap();
+        assert_eq!(cli.overrides.listen, Some(SocketAddress::new(String::from("::"), String::from("8080")).unwrap()));
+    }
+
+    #[test]
+    fn command_line_has_priority_over_environment() {
+        let env = [
+            (CONFIG_ENV, "/env.json"),
+            (ALGORITHM_ENV, "least_connections"),
+            (LOG_LEVEL_ENV, "warn")
+        ];
+        let cli = parse(&["--algorithm", "peak_ewma"], &env).unwrap();
+        assert_eq!(cli.config, PathBuf::from("/env.json"));
+        assert_eq!(cli.overrides.algorithm, Some(Algorithm::PeakEwma));
+        assert_eq!(cli.log_level, LogLevel::Warn);
+    }
+
+    #[test]
+    fn check_config_takes_an_optional_path() {
+        let cli = parse(&["--check-config", "other.json", "--probe"], &[]).unwrap();
+        assert_eq!(cli.command, Command::CheckConfig { probe: true });
+        assert_eq!(cli.config, PathBuf::from("other.json"));
+
+        let cli = parse(&["--config", "lb.json", "--check-config"], &[]).unwrap();
+        assert_eq!(cli.command, Command::CheckConfig { probe: false });
+        assert_eq!(cli.config, PathBuf::from("lb.json"));
+    }
+
+    #[test]
+    fn version_and_help() {
+        assert_eq!(parse(&["--version"], &[]).unwrap().command, Command::Version);
+        assert_eq!(parse(&["--listen", "bad", "-h"], &[]).unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn invalid_arguments_are_errors() {
+        let error = |args: &[&str], env: &[(&str, &str)]| parse(args, env).unwrap_err().argument;
+        assert_eq!(error(&["--listen", "localhost"], &[]), "--listen");
+        assert_eq!(error(&["--listen", "127.0.0.1:70000"], &[]), "--listen");
+        assert_eq!(error(&["--algorithm", "random"], &[]), "--algorithm");
+        assert_eq!(error(&["--log-level", "verbose"], &[]), "--log-level");
+        assert_eq!(error(&["--config"], &[]), "--config");
+        assert_eq!(error(&["--config", "--probe"], &[]), "--config");
+        assert_eq!(error(&["--port", "80"], &[]), "--port");
+        assert_eq!(error(&["--listen", "::1:8080"], &[]), "--listen");
+        assert_eq!(error(&[], &[(LISTEN_ENV, "nope")]), LISTEN_ENV);
+    }
+}
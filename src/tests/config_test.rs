@@ -0,0 +1,165 @@
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::{
+        server::{
+            app::ProxyMode,
+            socket_address::*
+        },
+        balancers::{
+            configure,
+            create_balancer,
+            config_error::ConfigError,
+            Algorithm,
+            Config,
+            TOO_MANY_SERVERS
+        }
+    };
+
+    /// Write the json in a temporary file, with a name unique for the test
+    fn config_file(name: &str, json: &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("load_balancer_config_{}_{}.json", name, std::process::id()));
+        std::fs::write(&path, json).unwrap();
+        path
+    }
+
+    fn configure_json(name: &str, json: &str) -> Result<Config, Vec<ConfigError>> {
+        let path = config_file(name, json);
+        let result = configure(&path);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    fn paths(errors: &[ConfigError]) -> Vec<&str> {
+        errors.iter().map(|error| error.path()).collect()
+    }
+
+    #[test]
+    fn valid_config_is_parsed() {
+        let config = configure_json("valid", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Servers": [
+                { "ipv4": "127.0.0.1", "port": "9000", "weight": 1 },
+                { "ipv4": "127.0.0.1", "port": "9001", "weight": 3 }
+            ],
+            "Mode": "http"
+        }"#).unwrap();
+        assert_eq!(config.listen_to, SocketAddress::new(String::from("127.0.0.1"), String::from("8080")).unwrap());
+        assert_eq!(config.servers.len(), 2);
+        assert_eq!(config.servers[1].1, 3);
+        assert_eq!(config.mode, ProxyMode::Http);
+        assert!(config.health_check.is_none());
+    }
+
+    #[test]
+    fn all_the_errors_are_reported_with_their_path() {
+        let errors = configure_json("errors", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1" },
+            "Servers": [
+                { "ipv4": "127.0.0.1", "port": "9000", "weight": 1 },
+                { "ipv4": "300.0.0.1", "port": "9001", "weight": 1 },
+                { "ipv4": "127.0.0.1", "port": "9002", "weight": "heavy" },
+                { "ipv4": "127.0.0.1", "port": "9003", "weight": 1, "http_check": { "status": [500, 200] } }
+            ],
+            "Mode": "udp",
+            "Health_check": { "interval": 0 },
+            "Retries": { "retry_on_reset": 1 }
+        }"#).unwrap_err();
+        assert_eq!(paths(&errors), vec![
+            "Listen_to.port",
+            "Servers[1].ipv4",
+            "Servers[2].weight",
+            "Servers[3].http_check.status",
+            "Mode",
+            "Health_check.interval",
+            "Retries.retry_on_reset"
+        ]);
+        assert!(matches!(errors[0], ConfigError::MissingKey { .. }));
+        assert_eq!(errors[1].to_string(), format!("Servers[1].ipv4: {}", IPV4_ERROR));
+    }
+
+    #[test]
+    fn missing_servers_and_empty_servers_are_errors() {
+        let errors = configure_json("no_servers", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" }
+        }"#).unwrap_err();
+        assert_eq!(paths(&errors), vec!["Servers"]);
+
+        let errors = configure_json("empty_servers", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Servers": []
+        }"#).unwrap_err();
+        assert_eq!(paths(&errors), vec!["Servers"]);
+    }
+
+    #[test]
+    fn unreadable_files_and_bad_json_are_errors() {
+        let missing = std::env::temp_dir().join("load_balancer_config_missing.json");
+        let errors = configure(&missing).unwrap_err();
+        assert!(matches!(&errors[..], [ConfigError::Io { path, .. }] if path.is_empty()));
+
+        let errors = configure_json("bad_json", "{ \"Servers\": [").unwrap_err();
+        assert!(matches!(&errors[..], [ConfigError::Json(_)]));
+
+        let errors = configure_json("missing_page", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Servers": [{ "ipv4": "127.0.0.1", "port": "9000", "weight": 1 }],
+            "Error_pages": { "502": "load_balancer_missing_page.html", "200": "ok.html" }
+        }"#).unwrap_err();
+        let mut paths = paths(&errors);
+        paths.sort();
+        assert_eq!(paths, vec!["Error_pages.200", "Error_pages.502"]);
+    }
+
+    #[test]
+    fn balancer_errors_have_the_path_of_the_servers() {
+        let servers = (0..300).map(|i| {
+            (i, (SocketAddress::new(String::from("127.0.0.1"), (9000 + i).to_string()).unwrap(), 1))
+        }).collect();
+        let error = create_balancer(Algorithm::WeightedRoundRobin, servers, &serde_json::Value::Null)
+            .err().unwrap();
+        assert_eq!(error.path(), "Servers");
+        assert_eq!(error.to_string(), format!("Servers: {}", TOO_MANY_SERVERS));
+    }
+
+    #[test]
+    fn ipv6_addresses_use_the_ipv6_key() {
+        let config = configure_json("ipv6", r#"{
+            "Listen_to": { "ipv6": "::", "port": "8080", "dual_stack": false },
+            "Servers": [
+                { "ipv6": "::1", "port": "9000", "weight": 1 },
+                { "ipv4": "127.0.0.1", "port": "9001", "weight": 1 }
+            ]
+        }"#).unwrap();
+        assert_eq!(config.listen_to.get(), "[::]:8080");
+        assert!(!config.dual_stack);
+        assert_eq!(config.servers[0].0.get(), "[::1]:9000");
+
+        let errors = configure_json("ipv6_errors", r#"{
+            "Listen_to": { "ipv4": "::", "port": "8080" },
+            "Servers": [
+                { "ipv4": "127.0.0.1", "ipv6": "::1", "port": "9000", "weight": 1 },
+                { "ipv6": "127.0.0.1", "port": "9001", "weight": 1 }
+            ]
+        }"#).unwrap_err();
+        assert_eq!(paths(&errors), vec!["Listen_to.ipv4", "Servers[0]", "Servers[1].ipv6"]);
+    }
+
+    #[test]
+    fn admin_listens_on_another_address() {
+        let config = configure_json("admin", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Admin": { "ipv6": "::1", "port": "8081" },
+            "Servers": [{ "ipv4": "127.0.0.1", "port": "9000", "weight": 1 }]
+        }"#).unwrap();
+        assert_eq!(config.admin.unwrap().get(), "[::1]:8081");
+
+        let errors = configure_json("admin_errors", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Admin": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Servers": [{ "ipv4": "127.0.0.1", "port": "9000", "weight": 1 }]
+        }"#).unwrap_err();
+        assert_eq!(paths(&errors), vec!["Admin"]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::{
+        server::socket_address::*,
+        balancers::{
+            LoadBalancer,
+            consistent_hash_load_balancer::load_balancer::*
+        },
+        tests::fixtures::*
+    };
+
+    const CLIENTS: u32 = 10_000;
+
+    fn create_load_balancer(servers: &[(usize, usize)]) -> ConsistentHashLB {
+        create_indexed_load_balancer(servers, |_| ())
+    }
+
+    fn assignments(balancer: &ConsistentHashLB) -> Vec<SocketAddress> {
+        (0..CLIENTS).map(|n| balancer.next_server(&client(n, 40000)).unwrap().clone()).collect()
+    }
+
+    #[test]
+    fn virtual_nodes_are_proportional_to_the_weight() {
+        let balancer = create_load_balancer(&[(0, 1), (1, 3)]);
+
+        assert_eq!(balancer.n_of_servers(), 2);
+        assert_eq!(balancer.n_of_points(), 4 * VIRTUAL_NODES_PER_WEIGHT);
+    }
+
+    #[test]
+    fn same_client_lands_on_same_server() {
+        let balancer = create_load_balancer(&[(0, 1), (1, 1), (2, 1)]);
+        for n in 0..100 {
+            let first = balancer.next_server(&client(n, 40000)).unwrap().clone();
+            // a new connection of the same client uses another port
+            assert_eq!(*balancer.next_server(&client(n, 40001)).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn clients_are_spread_according_to_the_weights() {
+        let balancer = create_load_balancer(&[(0, 1), (1, 1), (2, 2)]);
+        let mut hash = HashMap::new();
+        for server in assignments(&balancer) {
+            *hash.entry(server).or_insert(0) += 1;
+        }
+        let share = |i: usize| hash[&soc_addr(i)] as f64 / CLIENTS as f64;
+
+        assert!((share(0) - 0.25).abs() < 0.08, "share of server 0: {}", share(0));
+        assert!((share(1) - 0.25).abs() < 0.08, "share of server 1: {}", share(1));
+        assert!((share(2) - 0.50).abs() < 0.08, "share of server 2: {}", share(2));
+    }
+
+    #[test]
+    fn removing_a_server_only_remaps_its_clients() {
+        let before = assignments(&create_load_balancer(&[(0, 1), (1, 1), (2, 1), (3, 1)]));
+        let after = assignments(&create_load_balancer(&[(0, 1), (1, 1), (3, 1)]));
+
+        let removed = soc_addr(2);
+        for (old, new) in before.iter().zip(after.iter()) {
+            if *old != removed {
+                assert_eq!(old, new);
+            }
+        }
+    }
+
+    #[test]
+    fn adding_a_server_only_takes_clients_to_it() {
+        let before = assignments(&create_load_balancer(&[(0, 1), (1, 1), (2, 1)]));
+        let after = assignments(&create_load_balancer(&[(0, 1), (1, 1), (2, 1), (3, 1)]));
+        let moved = before.iter().zip(after.iter()).filter(|(old, new)| old != new).count();
+
+        let added = soc_addr(3);
+        for (old, new) in before.iter().zip(after.iter()) {
+            assert!(old == new || *new == added);
+        }
+        // about 1/4 of the clients must move to the new server
+        assert!(moved > (CLIENTS / 8) as usize && moved < (CLIENTS / 2) as usize, "moved: {}", moved);
+    }
+
+    #[test]
+    fn total_weight_over_the_max_returns_string_error() {
+        let mut balancer = *ConsistentHashLB::new(3).unwrap();
+        balancer.insert_socket_address(soc_addr(0), MAX_TOTAL_WEIGHT - 1).unwrap();
+        balancer.insert_socket_address(soc_addr(1), 1).unwrap();
+        assert_eq!(balancer.insert_socket_address(soc_addr(2), 1), Err(TOTAL_WEIGHT_TOO_HIGH));
+        assert_eq!(balancer.insert_socket_address(soc_addr(2), usize::MAX), Err(TOTAL_WEIGHT_TOO_HIGH));
+        assert_eq!(balancer.n_of_points(), MAX_TOTAL_WEIGHT * VIRTUAL_NODES_PER_WEIGHT);
+    }
+
+    #[test]
+    fn insertion_order_does_not_change_the_ring() {
+        let balancer = create_load_balancer(&[(0, 1), (1, 3), (2, 2)]);
+        let reversed = create_load_balancer(&[(2, 2), (1, 3), (0, 1)]);
+
+        assert_eq!(assignments(&balancer), assignments(&reversed));
+    }
+
+    #[test]
+    fn no_available_server_returns_none() {
+        let balancer = create_load_balancer(&[(0, 1), (1, 1)]);
+        let mut context = client(0, 40000);
+        context.exclude(soc_addr(0));
+        context.exclude(soc_addr(1));
+
+        assert_eq!(balancer.next_server(&context), None);
+    }
+}
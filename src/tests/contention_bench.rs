@@ -0,0 +1,100 @@
+/// Contention benchmark of the round robin selection.
+/// Run it with `cargo test --release contention_bench -- --ignored --nocapture`
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex, Barrier},
+        thread,
+        time::{Duration, Instant}
+    };
+    use crate::{
+        server::socket_address::*,
+        balancers::{
+            LoadBalancer,
+            load_balancer_factory,
+            standard_weighted_load_balancer::load_balancer::*
+        },
+        tests::fixtures::{context, soc_addr}
+    };
+
+    const THREADS: usize = 8;
+    const REQUESTS_PER_THREAD: usize = 1_000_000;
+    const WEIGHTS: [usize; 5] = [1, 2, 3, 1, 2];
+
+    /// The previous design, with a mutex for the array index
+    /// and a mutex for the weight index of every server
+    struct TwoMutexesLB {
+        addresses: Vec<(SocketAddress, usize, Mutex<usize>)>,
+        index: Mutex<usize>
+    }
+
+    impl TwoMutexesLB {
+        fn next_server(&self) -> &SocketAddress {
+            let mut idx = self.index.lock().unwrap();
+            loop {
+                let (socket_address, weight, counter) = &self.addresses[*idx];
+                let mut counter = counter.lock().unwrap();
+                if *counter < *weight {
+                    *counter += 1;
+                    return socket_address;
+                }
+                *counter = 0;
+                *idx = (*idx + 1) % self.addresses.len();
+            }
+        }
+    }
+
+    /// Return the time spent by THREADS threads calling `next_server` concurrently
+    fn measure<F>(next_server: F) -> Duration
+    where F: Fn() -> usize + Send + Sync + 'static {
+        let next_server = Arc::new(next_server);
+        let barrier = Arc::new(Barrier::new(THREADS + 1));
+        let handles: Vec<_> = (0..THREADS).map(|_| {
+            let next_server = Arc::clone(&next_server);
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                barrier.wait();
+                let mut checksum = 0;
+                for _ in 0..REQUESTS_PER_THREAD {
+                    checksum += next_server();
+                }
+                checksum
+            })
+        }).collect();
+
+        barrier.wait();
+        let start = Instant::now();
+        for handle in handles {
+            std::hint::black_box(handle.join().unwrap());
+        }
+        start.elapsed()
+    }
+
+    #[test]
+    #[ignore]
+    fn contention_bench() {
+        let mut atomic = load_balancer_factory::<WeightedRoundRobinLB>(WEIGHTS.len()).unwrap();
+        for (i, weight) in WEIGHTS.iter().enumerate() {
+            atomic.insert_socket_address(soc_addr(i), *weight).unwrap();
+        }
+        let mutexes = TwoMutexesLB {
+            addresses: WEIGHTS.iter().enumerate()
+                .map(|(i, weight)| (soc_addr(i), *weight, Mutex::new(0)))
+                .collect(),
+            index: Mutex::new(0)
+        };
+
+        let mutexes_time = measure(move || mutexes.next_server().port() as usize);
+        let context = context();
+        let atomic_time = measure(move || atomic.next_server(&context).unwrap().port() as usize);
+
+        let total = (THREADS * REQUESTS_PER_THREAD) as f64;
+        println!(
+            "{} threads x {} requests\n  two mutexes: {:?} ({:.1} ns/request)\n  atomic:      {:?} ({:.1} ns/request)\n  speedup:     {:.1}x",
+            THREADS, REQUESTS_PER_THREAD,
+            mutexes_time, mutexes_time.as_nanos() as f64 / total,
+            atomic_time, atomic_time.as_nanos() as f64 / total,
+            mutexes_time.as_secs_f64() / atomic_time.as_secs_f64()
+        );
+    }
+}
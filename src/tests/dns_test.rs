@@ -0,0 +1,261 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        net::IpAddr,
+        sync::{Arc, Mutex},
+        time::Duration
+    };
+    use tokio::{
+        io::AsyncWriteExt,
+        net::TcpListener,
+        sync::{mpsc, oneshot},
+        time::timeout
+    };
+    use crate::{
+        server::{
+            app::Server,
+            dns::*,
+            health_check::HttpCheck,
+            socket_address::SocketAddress
+        },
+        tests::fixtures::{configure_json, free_port, request}
+    };
+
+    /// A resolver with answers set by the test, given after a delay,
+    /// None makes the resolution fail
+    struct StubResolver {
+        answer: Mutex<Option<(Vec<IpAddr>, Option<Duration>)>>,
+        delay: Mutex<Duration>
+    }
+
+    impl StubResolver {
+        fn new(addresses: &[&str], ttl: Option<Duration>) -> Arc<Self> {
+            let resolver = Arc::new(StubResolver { answer: Mutex::new(None), delay: Mutex::new(Duration::ZERO) });
+            resolver.set(addresses, ttl);
+            resolver
+        }
+
+        fn set(&self, addresses: &[&str], ttl: Option<Duration>) {
+            let addresses = addresses.iter().map(|address| address.parse().unwrap()).collect();
+            *self.answer.lock().unwrap() = Some((addresses, ttl));
+        }
+
+        fn fail(&self) {
+            *self.answer.lock().unwrap() = None;
+        }
+
+        fn delay(&self, delay: Duration) {
+            *self.delay.lock().unwrap() = delay;
+        }
+    }
+
+    impl Resolver for StubResolver {
+        fn resolve<'a>(&'a self, _host: &'a str) -> ResolveFuture<'a> {
+            let answer = self.answer.lock().unwrap().clone();
+            let delay = *self.delay.lock().unwrap();
+            Box::pin(async move {
+                tokio::time::sleep(delay).await;
+                match answer {
+                    Some((addresses, ttl)) => Ok(Resolution::new(addresses, ttl)),
+                    None => Err(io::Error::new(io::ErrorKind::NotFound, "no such host"))
+                }
+            })
+        }
+    }
+
+    fn host(index: usize, name: &str, weight: usize) -> HostServer {
+        HostServer {
+            index,
+            host: String::from(name),
+            port: 8080,
+            weight,
+            http_check: None
+        }
+    }
+
+    fn ips(addresses: &[&str]) -> Vec<IpAddr> {
+        addresses.iter().map(|address| address.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn resolutions_are_sorted_without_duplicates() {
+        let resolution = Resolution::new(ips(&["10.0.0.2", "10.0.0.1", "10.0.0.2"]), None);
+        assert_eq!(resolution.addresses, ips(&["10.0.0.1", "10.0.0.2"]));
+    }
+
+    #[test]
+    fn the_ttl_is_respected_up_to_the_interval() {
+        let interval = Duration::from_secs(30);
+        assert_eq!(refresh_time(None, interval), interval);
+        assert_eq!(refresh_time(Some(Duration::from_secs(5)), interval), Duration::from_secs(5));
+        assert_eq!(refresh_time(Some(Duration::from_secs(300)), interval), interval);
+        assert_eq!(refresh_time(Some(Duration::ZERO), interval), MIN_TTL);
+    }
+
+    #[test]
+    fn every_address_of_a_host_has_its_weight_and_check() {
+        let mut checked = host(3, "api.internal", 2);
+        checked.http_check = Some(HttpCheck::default());
+        let hosts = [host(0, "db.internal", 5), checked];
+
+        let (servers, http_checks) = expand(&hosts, &[ips(&["10.0.0.1"]), ips(&["10.0.1.1", "::1"])]);
+        let socket_address = |ip: &str| SocketAddress::new(String::from(ip), String::from("8080")).unwrap();
+        assert_eq!(servers, vec![
+            (0, (socket_address("10.0.0.1"), 5)),
+            (3, (socket_address("10.0.1.1"), 2)),
+            (3, (socket_address("::1"), 2))
+        ]);
+        assert_eq!(http_checks.len(), 2);
+        assert!(http_checks.contains_key(&socket_address("::1")));
+    }
+
+    #[tokio::test]
+    async fn unresolved_hosts_are_errors_with_their_path() {
+        let hosts = [host(1, "api.internal", 1)];
+        let resolver = StubResolver::new(&["10.0.0.1"], None);
+        let resolutions = resolve_hosts(&hosts, &*resolver).await.unwrap();
+        assert_eq!(resolutions, vec![Resolution::new(ips(&["10.0.0.1"]), None)]);
+
+        resolver.set(&[], None);
+        let errors = resolve_hosts(&hosts, &*resolver).await.unwrap_err();
+        assert_eq!(errors[0].path(), "Servers[1].host");
+        resolver.fail();
+        let errors = resolve_hosts(&hosts, &*resolver).await.unwrap_err();
+        assert!(errors[0].to_string().starts_with("Servers[1].host: Can't resolve api.internal"));
+    }
+
+    #[tokio::test]
+    async fn only_the_changes_are_sent() {
+        let hosts = [host(0, "api.internal", 1)];
+        let resolver = StubResolver::new(&["10.0.0.1", "10.0.0.2"], None);
+        let resolutions = resolve_hosts(&hosts, &*resolver).await.unwrap();
+        let config = DnsConfig { interval: Duration::from_millis(20) };
+        let mut refresher = HostRefresher::spawn(&hosts, resolutions, resolver.clone(), config);
+
+        // the same records in another order, then a failure, aren't changes
+        resolver.set(&["10.0.0.2", "10.0.0.1"], None);
+        assert!(timeout(Duration::from_millis(100), refresher.next()).await.is_err());
+        resolver.fail();
+        assert!(timeout(Duration::from_millis(100), refresher.next()).await.is_err());
+
+        resolver.set(&["10.0.0.3"], None);
+        let update = timeout(Duration::from_secs(1), refresher.next()).await.unwrap();
+        assert_eq!(update, HostUpdate { index: 0, addresses: ips(&["10.0.0.3"]) });
+    }
+
+    #[test]
+    fn servers_can_have_a_hostname() {
+        let config = configure_json("dns_hosts", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Servers": [
+                { "ipv4": "127.0.0.1", "port": "9000", "weight": 1 },
+                { "host": "api.internal", "port": "9001", "weight": 3, "http_check": { "path": "/health" } }
+            ],
+            "Dns": { "interval": 5000 }
+        }"#).unwrap();
+        assert_eq!(config.servers.len(), 1);
+        assert_eq!(config.hosts.len(), 1);
+        assert_eq!(config.hosts[0].index, 1);
+        assert_eq!(config.hosts[0].weight, 3);
+        assert!(config.hosts[0].http_check.is_some());
+        assert!(config.health_check.is_some());
+        assert_eq!(config.dns.interval, Duration::from_secs(5));
+
+        let errors = configure_json("dns_hosts_errors", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Servers": [
+                { "host": "api.internal", "ipv4": "127.0.0.1", "port": "9000", "weight": 1 },
+                { "host": "api internal", "port": "9001", "weight": 1 },
+                { "host": "api.internal", "port": "99999", "weight": 1 }
+            ],
+            "Dns": { "interval": 0 }
+        }"#).unwrap_err();
+        let paths: Vec<_> = errors.iter().map(|error| error.path()).collect();
+        assert_eq!(paths, vec!["Servers[0]", "Servers[1].host", "Servers[2].port", "Dns.interval"]);
+    }
+
+    /// Accept the connections and write the name of the backend,
+    /// on any free port if `port` is 0
+    async fn backend(ip: &str, port: u16, name: &'static str) -> u16 {
+        let listener = TcpListener::bind((ip, port)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let _ = socket.write_all(name.as_bytes()).await;
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn the_servers_follow_the_changes_of_the_records() {
+        // the same port on two loopback addresses
+        let port = backend("127.0.0.1", 0, "old").await;
+        backend("127.0.0.2", port, "new").await;
+        let listen_port = free_port().await;
+
+        let config = configure_json("dns_server", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Servers": [{{ "host": "api.internal", "port": "{}", "weight": 1 }}],
+            "Dns": {{ "interval": 20 }}
+        }}"#, listen_port, port)).unwrap();
+        let resolver = StubResolver::new(&["127.0.0.1"], None);
+        let (stop, stopped) = oneshot::channel::<()>();
+        let (_reload_sender, reloads) = mpsc::channel(1);
+        let mut server = Server::new(config).with_resolver(resolver.clone());
+        let server = tokio::spawn(async move {
+            server.run(async { let _ = stopped.await; }, reloads).await
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(request(listen_port).await, "old");
+        resolver.set(&["127.0.0.2"], None);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(request(listen_port).await, "new");
+
+        stop.send(()).unwrap();
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn hosts_are_resolved_concurrently() {
+        let hosts = [host(0, "api.internal", 1), host(1, "db.internal", 1), host(2, "cache.internal", 1)];
+        let resolver = StubResolver::new(&["10.0.0.1"], None);
+        resolver.delay(Duration::from_millis(100));
+
+        let started = tokio::time::Instant::now();
+        assert_eq!(resolve_hosts(&hosts, &*resolver).await.unwrap().len(), 3);
+        assert!(started.elapsed() < Duration::from_millis(250), "elapsed: {:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn connections_are_accepted_while_a_reload_is_resolved() {
+        let port = backend("127.0.0.1", 0, "old").await;
+        backend("127.0.0.2", port, "new").await;
+        let listen_port = free_port().await;
+        let config = |name, server: &str| configure_json(name, &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Servers": [{{ {}, "port": "{}", "weight": 1 }}]
+        }}"#, listen_port, server, port)).unwrap();
+        let resolver = StubResolver::new(&["127.0.0.2"], None);
+        let (stop, stopped) = oneshot::channel::<()>();
+        let (reload_sender, reloads) = mpsc::channel(1);
+        let mut server = Server::new(config("dns_reload", r#""ipv4": "127.0.0.1""#)).with_resolver(resolver.clone());
+        let server = tokio::spawn(async move {
+            server.run(async { let _ = stopped.await; }, reloads).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        resolver.delay(Duration::from_millis(300));
+        reload_sender.send(config("dns_reload_host", r#""host": "api.internal""#)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let response = timeout(Duration::from_millis(100), request(listen_port)).await;
+        assert_eq!(response.unwrap(), "old");
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(request(listen_port).await, "new");
+
+        stop.send(()).unwrap();
+        assert!(server.await.unwrap().is_ok());
+    }
+}
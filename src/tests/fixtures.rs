@@ -0,0 +1,110 @@
+use std::net::SocketAddr;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot},
+    task::JoinHandle
+};
+use crate::{
+    server::{app::Server, socket_address::SocketAddress},
+    balancers::{configure, config_error::ConfigError, Config, LoadBalancer, RequestContext}
+};
+
+/// Return the context of a connection from the same client
+pub fn context() -> RequestContext {
+    RequestContext::new("127.0.0.1:50000".parse().unwrap())
+}
+
+/// Return the context of the n-th of many different clients
+pub fn client(n: u32, port: u16) -> RequestContext {
+    let ip = std::net::Ipv4Addr::from(0xC0A8_0000 + n);
+    RequestContext::new(SocketAddr::from((ip, port)))
+}
+
+/// Return the socket address of the i-th server, 127.0.0.1:(9000 + i)
+pub fn soc_addr(i: usize) -> SocketAddress {
+    SocketAddress::new(String::from("127.0.0.1"), (9000 + i).to_string()).unwrap()
+}
+
+/// Create a balancer with a server for each weight, the i-th one is `soc_addr(i)`
+pub fn create_load_balancer<T: LoadBalancer>(weights: &[usize]) -> T {
+    let servers: Vec<_> = weights.iter().copied().enumerate().collect();
+    create_indexed_load_balancer(&servers, |_| ())
+}
+
+/// Create a balancer with the given servers.
+/// # Arguments
+///
+/// * `servers` - the index of `soc_addr` and the weight of every server
+/// * `configure` - called before inserting the servers, e.g. for the options
+pub fn create_indexed_load_balancer<T: LoadBalancer>(servers: &[(usize, usize)], configure: impl FnOnce(&mut T)) -> T {
+    let mut balancer = *T::new(servers.len()).unwrap();
+    configure(&mut balancer);
+    for (i, weight) in servers {
+        balancer.insert_socket_address(soc_addr(*i), *weight).unwrap();
+    }
+    balancer
+}
+
+/// Read a configuration from a json string, through a temporary file
+/// named after the test
+pub fn configure_json(name: &str, json: &str) -> Result<Config, Vec<ConfigError>> {
+    let path = std::env::temp_dir()
+        .join(format!("load_balancer_{}_{}.json", name, std::process::id()));
+    std::fs::write(&path, json).unwrap();
+    let config = configure(&path);
+    std::fs::remove_file(&path).unwrap();
+    config
+}
+
+/// Run the balancer with the configuration of a json string,
+/// until the returned sender is used
+pub fn start(name: &str, json: &str) -> (oneshot::Sender<()>, JoinHandle<()>) {
+    let config = configure_json(name, json).unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(async move {
+        let (_reload_sender, reloads) = mpsc::channel(1);
+        Server::new(config).run(async { let _ = stopped.await; }, reloads).await.unwrap();
+    });
+    (stop, server)
+}
+
+/// Return a port that was free on 127.0.0.1
+pub async fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port()
+}
+
+/// Accept the connections and write the name of the backend, return the port
+pub async fn backend(name: &'static str) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let _ = socket.write_all(name.as_bytes()).await;
+        }
+    });
+    port
+}
+
+/// Connect to the port and read everything until the close
+pub async fn request(port: u16) -> String {
+    let mut socket = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+/// Send a request to the admin API, return the status and the json body
+pub async fn admin(port: u16, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let mut socket = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: admin\r\nContent-Length: {}\r\n\r\n{}",
+        method, path, body.len(), body
+    );
+    socket.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head[9..12].parse().unwrap(), serde_json::from_str(body).unwrap())
+}
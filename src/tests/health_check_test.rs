@@ -0,0 +1,167 @@
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener
+    };
+    use crate::{
+        server::{
+            socket_address::*,
+            backend_state::*,
+            health_check::*
+        },
+        balancers::{
+            Algorithm,
+            RequestContext,
+            create_balancer,
+            registry::registrations
+        }
+    };
+
+    fn servers() -> Vec<(SocketAddress, usize)> {
+        (0..3).map(|i| {
+            (SocketAddress::new(String::from("127.0.0.1"), (9000 + i).to_string()).unwrap(), i + 1)
+        }).collect()
+    }
+
+    /// Marks the server 9001 as down and checks that the balancer never
+    /// returns it, then marks all of them as down and expects None
+    fn unhealthy_servers_are_skipped(algorithm: Algorithm) {
+        let servers = servers();
+        let states = Arc::new(BackendStates::new(&servers));
+        let down = servers[1].0.clone();
+        let balancer = create_balancer(algorithm, servers.iter().cloned().enumerate().collect(), &serde_json::Value::Null).unwrap();
+        states.get(&down).unwrap().set_healthy(false);
+
+        for client in 0..100 {
+            let context = RequestContext::new(format!("10.0.0.{}:40000", client).parse().unwrap())
+                .with_states(Arc::clone(&states));
+            let server = balancer.next_server(&context).unwrap().clone();
+            assert_ne!(server, down);
+            balancer.release(&server);
+        }
+
+        for (socket_address, _) in &servers {
+            states.get(socket_address).unwrap().set_healthy(false);
+        }
+        let context = RequestContext::new("10.0.0.1:40000".parse().unwrap())
+            .with_states(Arc::clone(&states));
+        assert!(balancer.next_server(&context).is_none());
+    }
+
+    #[test]
+    fn every_balancer_skips_unhealthy_servers() {
+        for registration in registrations() {
+            unhealthy_servers_are_skipped(registration.algorithm);
+        }
+    }
+
+    #[test]
+    fn server_goes_down_after_fall_failures() {
+        let state = BackendState::new();
+
+        assert_eq!(state.record_check(false, 2, 3), None);
+        assert_eq!(state.record_check(false, 2, 3), None);
+        assert!(state.is_available());
+        assert_eq!(state.record_check(false, 2, 3), Some(false));
+        assert!(!state.is_available());
+    }
+
+    #[test]
+    fn server_goes_up_after_rise_successes() {
+        let state = BackendState::new();
+        state.set_healthy(false);
+
+        assert_eq!(state.record_check(true, 2, 3), None);
+        // a failure resets the consecutive successes
+        assert_eq!(state.record_check(false, 2, 3), None);
+        assert_eq!(state.record_check(true, 2, 3), None);
+        assert_eq!(state.record_check(true, 2, 3), Some(true));
+        assert!(state.is_available());
+    }
+
+    #[tokio::test]
+    async fn tcp_check_depends_on_the_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        let socket_address = SocketAddress::new(String::from("127.0.0.1"), port).unwrap();
+
+        assert!(check_tcp(&socket_address, Duration::from_secs(1)).await);
+        drop(listener);
+        assert!(!check_tcp(&socket_address, Duration::from_secs(1)).await);
+    }
+
+    /// Starts a server that answers every connection with the given response
+    /// and sends back the received request head on the channel
+    async fn http_server(response: &'static str) -> (SocketAddress, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = vec![0; 1024];
+                let n = socket.read(&mut request).await.unwrap();
+                let _ = sender.send(String::from_utf8_lossy(&request[..n]).into_owned());
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (SocketAddress::new(String::from("127.0.0.1"), port).unwrap(), receiver)
+    }
+
+    #[tokio::test]
+    async fn http_check_sends_the_configured_request() {
+        let (socket_address, mut requests) = http_server("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+        let check = HttpCheck {
+            path: String::from("/health"),
+            method: String::from("HEAD"),
+            host: Some(String::from("api.internal")),
+            ..HttpCheck::default()
+        };
+
+        assert!(check_http(&socket_address, &check, Duration::from_secs(1)).await);
+        let request = requests.recv().await.unwrap();
+        assert!(request.starts_with("HEAD /health HTTP/1.1\r\n"));
+        assert!(request.contains("Host: api.internal\r\n"));
+    }
+
+    #[tokio::test]
+    async fn http_check_matches_the_status_range() {
+        let (socket_address, _requests) = http_server("HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n").await;
+        let mut check = HttpCheck::default();
+
+        assert!(!check_http(&socket_address, &check, Duration::from_secs(1)).await);
+        check.status = (500, 503);
+        assert!(check_http(&socket_address, &check, Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn http_check_matches_the_body() {
+        let (socket_address, _requests) = http_server(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n7\r\nstatus:\r\n3\r\n ok\r\n0\r\n\r\n"
+        ).await;
+        let mut check = HttpCheck {
+            body: Some(String::from("status: ok")),
+            ..HttpCheck::default()
+        };
+
+        assert!(check_http(&socket_address, &check, Duration::from_secs(1)).await);
+        check.body = Some(String::from("status: broken"));
+        assert!(!check_http(&socket_address, &check, Duration::from_secs(1)).await);
+    }
+
+    #[tokio::test]
+    async fn http_check_fails_on_timeout() {
+        // accepts the connection but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        let socket_address = SocketAddress::new(String::from("127.0.0.1"), port).unwrap();
+        let check = HttpCheck {
+            timeout: Some(Duration::from_millis(100)),
+            ..HttpCheck::default()
+        };
+
+        assert!(!check_http(&socket_address, &check, Duration::from_secs(10)).await);
+        drop(listener);
+    }
+}
@@ -0,0 +1,272 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream}
+    };
+    use crate::{server::http::*, tests::fixtures::*};
+
+    async fn head_of(raw: &[u8]) -> std::io::Result<Option<Head>> {
+        let mut reader = raw;
+        read_head(&mut reader).await
+    }
+
+    #[tokio::test]
+    async fn should_parse_request_head() {
+        let raw = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nAccept: */*\r\n\r\nbody";
+        let head = head_of(raw).await.unwrap().unwrap();
+
+        assert_eq!(head.method(), "GET");
+        assert_eq!(head.start_line.1, "/index.html");
+        assert_eq!(head.version, Version::Http11);
+        assert_eq!(head.header("host"), Some("example.com"));
+        assert_eq!(head.raw, raw[..raw.len() - 4].to_vec());
+        assert!(head.keep_alive());
+        assert_eq!(head.body_length(true).unwrap(), BodyLength::Empty);
+    }
+
+    #[tokio::test]
+    async fn should_skip_empty_lines_before_request() {
+        let head = head_of(b"\r\n\r\nGET / HTTP/1.0\r\n\r\n").await.unwrap().unwrap();
+
+        assert_eq!(head.version, Version::Http10);
+        assert!(!head.keep_alive());
+    }
+
+    #[tokio::test]
+    async fn should_return_none_on_closed_connection() {
+        assert!(head_of(b"").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_truncated_head() {
+        assert!(head_of(b"GET / HTTP/1.1\r\nHost: a\r\n").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_unsupported_version() {
+        assert!(head_of(b"GET / HTTP/2.0\r\n\r\n").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_folded_header() {
+        assert!(head_of(b"GET / HTTP/1.1\r\nA: b\r\n c\r\n\r\n").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_too_large_head() {
+        let mut raw = b"GET / HTTP/1.1\r\n".to_vec();
+        raw.extend(vec![b'a'; MAX_HEAD_SIZE as usize]);
+        assert!(head_of(&raw).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn transfer_encoding_wins_over_content_length() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\nTransfer-Encoding: gzip, chunked\r\n\r\n";
+        let head = head_of(raw).await.unwrap().unwrap();
+
+        assert_eq!(head.body_length(false).unwrap(), BodyLength::Chunked);
+        assert!(!String::from_utf8(head.forwarded()).unwrap().contains("Content-Length"));
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_request_with_both_lengths() {
+        let raw = b"POST / HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let head = head_of(raw).await.unwrap().unwrap();
+
+        let error = head.body_length(true).unwrap_err();
+        assert_eq!(error.to_string(), AMBIGUOUS_LENGTH);
+    }
+
+    #[tokio::test]
+    async fn hop_by_hop_headers_are_not_forwarded() {
+        let raw = b"GET /a HTTP/1.1\r\nHost: example.com\r\nConnection: keep-alive, X-Secret\r\n\
+            Keep-Alive: timeout=5\r\nX-Secret: 1\r\nTE: trailers\r\nUpgrade: websocket\r\nAccept: */*\r\n\r\n";
+        let head = head_of(raw).await.unwrap().unwrap();
+
+        assert_eq!(
+            String::from_utf8(head.forwarded()).unwrap(),
+            "GET /a HTTP/1.1\r\nConnection: keep-alive\r\nHost: example.com\r\nAccept: */*\r\n\r\n"
+        );
+
+        // the upgrade is forwarded when it's requested
+        let raw = b"GET /ws HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\n";
+        let head = head_of(raw).await.unwrap().unwrap();
+        assert_eq!(
+            String::from_utf8(head.forwarded()).unwrap(),
+            "GET /ws HTTP/1.1\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_different_content_lengths() {
+        let raw = b"POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\n";
+        let head = head_of(raw).await.unwrap().unwrap();
+
+        assert!(head.body_length(true).is_err());
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_signed_content_length() {
+        for value in ["+5", "-5", "5 5", "0x5", ""] {
+            let raw = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", value);
+            let head = head_of(raw.as_bytes()).await.unwrap().unwrap();
+
+            assert!(head.body_length(true).is_err(), "Content-Length: {}", value);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_header_name_that_is_not_a_token() {
+        for name in ["Transfer-Encoding\t", "Transfer-Encoding ", "Content Length", "Host\"", "(Host)", ""] {
+            let raw = format!("POST / HTTP/1.1\r\n{}: chunked\r\n\r\n", name);
+
+            assert!(head_of(raw.as_bytes()).await.is_err(), "name: {:?}", name);
+        }
+        assert!(head_of(b"GET / HTTP/1.1\r\nX-Custom_Name.1!: a\r\n\r\n").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn response_without_length_ends_on_close() {
+        let head = head_of(b"HTTP/1.1 200 OK\r\n\r\n").await.unwrap().unwrap();
+
+        assert_eq!(head.status(), 200);
+        assert_eq!(head.response_body_length("GET").unwrap(), BodyLength::UntilClose);
+        assert_eq!(head.response_body_length("HEAD").unwrap(), BodyLength::Empty);
+    }
+
+    #[tokio::test]
+    async fn response_204_has_no_body() {
+        let head = head_of(b"HTTP/1.1 204 No Content\r\nContent-Length: 10\r\n\r\n")
+            .await.unwrap().unwrap();
+
+        assert_eq!(head.response_body_length("GET").unwrap(), BodyLength::Empty);
+    }
+
+    #[tokio::test]
+    async fn should_copy_fixed_body_only() {
+        let mut reader: &[u8] = b"hello world";
+        let mut writer = Vec::new();
+        let n = copy_body(&mut reader, &mut writer, BodyLength::Fixed(5)).await.unwrap();
+
+        assert_eq!(n, 5);
+        assert_eq!(writer, b"hello");
+        assert_eq!(reader, b" world");
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_short_fixed_body() {
+        let mut reader: &[u8] = b"hel";
+        let mut writer = Vec::new();
+
+        assert!(copy_body(&mut reader, &mut writer, BodyLength::Fixed(5)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_copy_chunked_body_with_trailers() {
+        let body = b"5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nTrailer: x\r\n\r\n";
+        let mut raw = body.to_vec();
+        raw.extend(b"GET / HTTP/1.1\r\n\r\n");
+        let mut reader: &[u8] = &raw;
+        let mut writer = Vec::new();
+        let n = copy_body(&mut reader, &mut writer, BodyLength::Chunked).await.unwrap();
+
+        assert_eq!(n, body.len() as u64);
+        assert_eq!(writer, body.to_vec());
+        assert_eq!(reader, b"GET / HTTP/1.1\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_malformed_chunk() {
+        let mut reader: &[u8] = b"5\r\nhelloXX0\r\n\r\n";
+        let mut writer = Vec::new();
+
+        assert!(copy_body(&mut reader, &mut writer, BodyLength::Chunked).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_not_pass_chunk_size_that_is_not_hex() {
+        for line in ["+5", "+a", " 5", "5 ", "5 ;ext", "0x5", ";ext", "5\t"] {
+            let body = format!("{}\r\nhello\r\n0\r\n\r\n", line);
+            let mut writer = Vec::new();
+            let mut reader = body.as_bytes();
+            assert!(copy_body(&mut reader, &mut writer, BodyLength::Chunked).await.is_err(), "size: {:?}", line);
+            assert!(writer.is_empty());
+            let mut reader = body.as_bytes();
+            assert!(read_body(&mut reader, BodyLength::Chunked, 1024).await.is_err(), "size: {:?}", line);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_safe_methods_are_idempotent() {
+        for (method, idempotent) in [("GET", true), ("PUT", true), ("DELETE", true), ("POST", false), ("PATCH", false)] {
+            let raw = format!("{} / HTTP/1.1\r\n\r\n", method);
+            let head = head_of(raw.as_bytes()).await.unwrap().unwrap();
+            assert_eq!(head.is_idempotent(), idempotent, "{}", method);
+        }
+    }
+
+    #[tokio::test]
+    async fn error_response_has_a_default_body() {
+        let mut response = Vec::new();
+        write_error_response(&mut response, 503, &ErrorPages::default(), true).await.unwrap();
+
+        let head = head_of(&response).await.unwrap().unwrap();
+        assert_eq!(head.status(), 503);
+        assert_eq!(head.start_line.2, "Service Unavailable");
+        assert!(!head.keep_alive());
+        assert!(response.ends_with(b"\r\n\r\n503 Service Unavailable\n"));
+    }
+
+    #[tokio::test]
+    async fn error_response_uses_the_custom_page() {
+        let path = std::env::temp_dir().join(format!("load_balancer_502_{}.html", std::process::id()));
+        std::fs::write(&path, "<h1>down</h1>").unwrap();
+        let mut pages = ErrorPages::default();
+        pages.insert(502, ErrorPage::from_file(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let mut response = Vec::new();
+        write_error_response(&mut response, 502, &pages, true).await.unwrap();
+        let head = head_of(&response).await.unwrap().unwrap();
+        assert_eq!(head.header("Content-Type"), Some("text/html; charset=utf-8"));
+        assert_eq!(head.body_length(false).unwrap(), BodyLength::Fixed(13));
+        assert!(response.ends_with(b"<h1>down</h1>"));
+
+        // a HEAD request gets the same head without the body
+        let mut response = Vec::new();
+        write_error_response(&mut response, 502, &pages, false).await.unwrap();
+        assert!(response.ends_with(b"\r\n\r\n"));
+        assert_eq!(head_of(&response).await.unwrap().unwrap().header("Content-Length"), Some("13"));
+    }
+
+    #[tokio::test]
+    async fn error_response_does_not_wait_for_the_request_body() {
+        // the server closes the connection after the head
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let _ = socket.read(&mut [0; 1024]).await;
+        });
+        let listen_port = free_port().await;
+        let (stop, server) = start("http_early_error", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Mode": "http",
+            "Servers": [{{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}]
+        }}"#, listen_port, backend_port));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // the body is never sent
+        let mut client = TcpStream::connect(("127.0.0.1", listen_port)).await.unwrap();
+        client.write_all(b"POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 1000\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        tokio::time::timeout(Duration::from_secs(1), client.read_to_string(&mut response))
+            .await.expect("the error response waited for the body").unwrap();
+        assert!(response.starts_with("HTTP/1.1 502"), "response: {}", response);
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+}
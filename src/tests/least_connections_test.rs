@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::{
+        balancers::{
+            LoadBalancer,
+            load_balancer_factory,
+            ZERO_OR_NEGATIVE_SERVERS,
+            least_connections_load_balancer::load_balancer::*
+        },
+        tests::fixtures::*
+    };
+
+    #[test]
+    fn zero_servers_return_string_error() {
+        match load_balancer_factory::<LeastConnectionsLB>(0) {
+            Err(e) => assert_eq!(ZERO_OR_NEGATIVE_SERVERS, e),
+            Ok(_) => panic!("zero_servers_return_string_error")
+        }
+    }
+
+    #[test]
+    fn equal_weights_spread_the_connections() {
+        let balancer: LeastConnectionsLB = create_load_balancer(&[1, 1, 1]);
+        let mut hash = HashMap::new();
+        for _ in 0..9 {
+            *hash.entry(balancer.next_server(&context()).unwrap().get()).or_insert(0) += 1;
+        }
+
+        assert_eq!(balancer.n_of_servers(), 3);
+        assert_eq!(hash.len(), 3);
+        assert!(hash.values().all(|n| *n == 3));
+    }
+
+    #[test]
+    fn in_flight_connections_follow_the_weights() {
+        let balancer: LeastConnectionsLB = create_load_balancer(&[1, 3]);
+        for _ in 0..8 {
+            balancer.next_server(&context()).unwrap();
+        }
+
+        assert_eq!(balancer.active_connections(&soc_addr(0)), 2);
+        assert_eq!(balancer.active_connections(&soc_addr(1)), 6);
+    }
+
+    #[test]
+    fn released_server_is_chosen_again() {
+        let balancer: LeastConnectionsLB = create_load_balancer(&[1, 1]);
+        let first = balancer.next_server(&context()).unwrap().clone();
+        let second = balancer.next_server(&context()).unwrap().clone();
+        assert_ne!(first, second);
+
+        // both servers have 1 connection, the released one becomes the least loaded
+        balancer.release(&second);
+        assert_eq!(balancer.active_connections(&second), 0);
+        assert_eq!(*balancer.next_server(&context()).unwrap(), second);
+        assert_eq!(balancer.active_connections(&first), 1);
+        assert_eq!(balancer.active_connections(&second), 1);
+    }
+
+    #[test]
+    fn release_never_goes_below_zero() {
+        let balancer: LeastConnectionsLB = create_load_balancer(&[1]);
+        balancer.release(&soc_addr(0));
+
+        assert_eq!(balancer.active_connections(&soc_addr(0)), 0);
+    }
+
+    #[test]
+    fn in_flight_connections_are_kept_by_the_next_balancer() {
+        let previous: LeastConnectionsLB = create_load_balancer(&[1, 1]);
+        for _ in 0..4 {
+            previous.next_server(&context()).unwrap();
+        }
+        let mut balancer: LeastConnectionsLB = create_load_balancer(&[1, 1, 1]);
+        balancer.inherit(&previous);
+        assert_eq!(balancer.active_connections(&soc_addr(0)), 2);
+        assert_eq!(balancer.active_connections(&soc_addr(2)), 0);
+
+        // the connections of the old balancer are released on the new one
+        previous.release(&soc_addr(0));
+        assert_eq!(balancer.active_connections(&soc_addr(0)), 1);
+        for _ in 0..2 {
+            assert_eq!(*balancer.next_server(&context()).unwrap(), soc_addr(2));
+        }
+    }
+}
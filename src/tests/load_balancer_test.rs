@@ -8,7 +8,8 @@ mod tests {
             LoadBalancer,
             load_balancer_factory,
             standard_weighted_load_balancer::load_balancer::*
-        }
+        },
+        tests::fixtures::context
     };
 
     fn add_soc_addr(wrrlb: &mut WeightedRoundRobinLB) -> Result<(), &'static str> {
@@ -19,6 +20,7 @@ mod tests {
         )
     }
 
+    #[allow(clippy::needless_return)]
     fn create_filled_load_balancer() -> WeightedRoundRobinLB {
         let dim = 5;
         let mut b = load_balancer_factory::<WeightedRoundRobinLB>(dim).unwrap();
@@ -120,7 +122,7 @@ mod tests {
 
             let handle = thread::spawn(move || {
                 for _ in 0..(servers_number+1) {
-                    let mut counter = hash.entry(balancer.next_server().get()).or_insert(0);
+                    let mut counter = hash.entry(balancer.next_server(&context()).unwrap().get()).or_insert(0);
                     *counter += 1;
                 }
             });
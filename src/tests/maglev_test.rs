@@ -0,0 +1,98 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        server::socket_address::*,
+        balancers::{
+            LoadBalancer,
+            load_balancer_factory,
+            maglev_load_balancer::load_balancer::*
+        },
+        tests::fixtures::*
+    };
+
+    const CLIENTS: u32 = 20_000;
+    const TABLE_SIZE: usize = 5003;
+
+    fn create_load_balancer(servers: &[(usize, usize)]) -> MaglevLB {
+        create_indexed_load_balancer(servers, |balancer: &mut MaglevLB| balancer.set_table_size(TABLE_SIZE).unwrap())
+    }
+
+    fn assignments(balancer: &MaglevLB) -> Vec<SocketAddress> {
+        (0..CLIENTS).map(|n| balancer.next_server(&client(n, 40000)).unwrap().clone()).collect()
+    }
+
+    #[test]
+    fn table_size_must_be_prime() {
+        let mut balancer = load_balancer_factory::<MaglevLB>(3).unwrap();
+
+        assert_eq!(balancer.set_table_size(65536), Err(INVALID_TABLE_SIZE));
+        assert_eq!(balancer.set_table_size(2), Err(INVALID_TABLE_SIZE));
+        assert_eq!(balancer.set_table_size(65537), Ok(()));
+    }
+
+    #[test]
+    fn table_size_is_read_from_the_options() {
+        let mut balancer = load_balancer_factory::<MaglevLB>(3).unwrap();
+
+        assert_eq!(balancer.set_options(&serde_json::json!({"table_size": 100})), Err(INVALID_TABLE_SIZE));
+        assert_eq!(balancer.set_options(&serde_json::json!({"table_size": 101})), Ok(()));
+        assert_eq!(balancer.set_options(&serde_json::Value::Null), Ok(()));
+    }
+
+    #[test]
+    fn every_entry_of_the_table_is_balanced() {
+        let balancer = create_load_balancer(&[(0, 1), (1, 1), (2, 1), (3, 1), (4, 1)]);
+        let entries = balancer.entries_per_server();
+
+        assert_eq!(balancer.n_of_servers(), 5);
+        assert_eq!(entries.iter().sum::<usize>(), TABLE_SIZE);
+        // Maglev gives an almost perfect balance of the entries
+        for n in entries {
+            assert!(n.abs_diff(TABLE_SIZE / 5) <= 1, "entries: {}", n);
+        }
+    }
+
+    #[test]
+    fn entries_follow_the_weights() {
+        let balancer = create_load_balancer(&[(0, 1), (1, 3), (2, 0)]);
+        let entries = balancer.entries_per_server();
+
+        assert_eq!(entries[2], 0);
+        assert!(entries[0].abs_diff(TABLE_SIZE / 4) <= 1, "entries: {}", entries[0]);
+    }
+
+    #[test]
+    fn clients_are_balanced_across_servers() {
+        let balancer = create_load_balancer(&[(0, 1), (1, 1), (2, 1), (3, 1)]);
+        let assignments = assignments(&balancer);
+        for i in 0..4 {
+            let server = soc_addr(i);
+            let share = assignments.iter().filter(|s| **s == server).count() as f64 / CLIENTS as f64;
+            assert!((share - 0.25).abs() < 0.03, "share of server {}: {}", i, share);
+        }
+    }
+
+    #[test]
+    fn same_client_lands_on_same_server() {
+        let balancer = create_load_balancer(&[(0, 1), (1, 1), (2, 1)]);
+
+        assert_eq!(assignments(&balancer), assignments(&balancer));
+    }
+
+    #[test]
+    fn removing_a_server_remaps_few_other_clients() {
+        let servers: Vec<(usize, usize)> = (0..10).map(|i| (i, 1)).collect();
+        let before = assignments(&create_load_balancer(&servers));
+        let after = assignments(&create_load_balancer(&[&servers[..3], &servers[4..]].concat()));
+
+        let removed = soc_addr(3);
+        let of_removed = before.iter().filter(|s| **s == removed).count();
+        let remapped = before.iter().zip(after.iter()).filter(|(old, new)| old != new).count();
+
+        // the clients of the removed server must move, the others
+        // should stay where they are (a few % of disruption is allowed)
+        assert!(after.iter().all(|s| *s != removed));
+        let others_moved = (remapped - of_removed) as f64 / (CLIENTS as usize - of_removed) as f64;
+        assert!(others_moved < 0.05, "fraction of other clients remapped: {}", others_moved);
+    }
+}
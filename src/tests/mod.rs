@@ -1,2 +1,24 @@
+#[cfg(test)]
+mod fixtures;
 mod socket_address_test;
-mod load_balancer_test;
\ No newline at end of file
+mod load_balancer_test;
+mod http_test;
+mod least_connections_test;
+mod smooth_weighted_test;
+mod contention_bench;
+mod consistent_hash_test;
+mod maglev_test;
+mod power_of_two_choices_test;
+mod peak_ewma_test;
+mod health_check_test;
+mod outlier_detection_test;
+mod retry_test;
+mod timeouts_test;
+mod shutdown_test;
+mod config_test;
+mod validation_test;
+mod cli_test;
+mod registry_test;
+mod dns_test;
+mod admin_test;
+mod reload_test;
\ No newline at end of file
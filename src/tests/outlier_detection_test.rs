@@ -0,0 +1,186 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::{mpsc, oneshot}
+    };
+    use crate::{
+        server::{
+            app::Server,
+            socket_address::*,
+            backend_state::*,
+            outlier_detection::*
+        },
+        tests::fixtures::{admin, configure_json, free_port}
+    };
+
+    fn servers(n: usize) -> Vec<(SocketAddress, usize)> {
+        (0..n).map(|i| {
+            (SocketAddress::new(String::from("127.0.0.1"), (9000 + i).to_string()).unwrap(), 1)
+        }).collect()
+    }
+
+    fn config() -> OutlierDetectionConfig {
+        OutlierDetectionConfig {
+            consecutive_failures: 3,
+            ..OutlierDetectionConfig::default()
+        }
+    }
+
+    #[test]
+    fn server_is_ejected_after_consecutive_failures() {
+        let servers = servers(4);
+        let states = BackendStates::new(&servers).with_outlier_detection(Some(config()));
+        let server = &servers[0].0;
+
+        states.report_failure(server);
+        states.report_failure(server);
+        assert!(states.is_available(server));
+        states.report_failure(server);
+        assert!(!states.is_available(server));
+        assert_eq!(states.get(server).unwrap().total_ejections(), 1);
+    }
+
+    #[test]
+    fn success_resets_the_failures() {
+        let servers = servers(4);
+        let states = BackendStates::new(&servers).with_outlier_detection(Some(config()));
+        let server = &servers[0].0;
+
+        states.report_failure(server);
+        states.report_failure(server);
+        states.report_success(server);
+        states.report_failure(server);
+        states.report_failure(server);
+        assert!(states.is_available(server));
+    }
+
+    #[test]
+    fn failures_out_of_the_window_are_not_counted() {
+        let servers = servers(4);
+        let states = BackendStates::new(&servers).with_outlier_detection(Some(OutlierDetectionConfig {
+            window: Duration::from_millis(20),
+            ..config()
+        }));
+        let server = &servers[0].0;
+
+        states.report_failure(server);
+        states.report_failure(server);
+        std::thread::sleep(Duration::from_millis(40));
+        states.report_failure(server);
+        assert!(states.is_available(server));
+    }
+
+    #[test]
+    fn disabled_detection_never_ejects() {
+        let servers = servers(4);
+        let states = BackendStates::new(&servers);
+        let server = &servers[0].0;
+
+        for _ in 0..100 {
+            states.report_failure(server);
+        }
+        assert!(states.is_available(server));
+    }
+
+    #[test]
+    fn ejection_time_doubles_up_to_the_max() {
+        let config = OutlierDetectionConfig {
+            base_ejection_time: Duration::from_secs(10),
+            max_ejection_time: Duration::from_secs(60),
+            ..config()
+        };
+
+        assert_eq!(config.ejection_time(0), Duration::from_secs(10));
+        assert_eq!(config.ejection_time(1), Duration::from_secs(20));
+        assert_eq!(config.ejection_time(2), Duration::from_secs(40));
+        assert_eq!(config.ejection_time(3), Duration::from_secs(60));
+        assert_eq!(config.ejection_time(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn max_ejection_percent_keeps_part_of_the_pool() {
+        let servers = servers(4);
+        let states = BackendStates::new(&servers).with_outlier_detection(Some(config()));
+
+        for (server, _) in &servers {
+            for _ in 0..3 {
+                states.report_failure(server);
+            }
+        }
+        // 50% of 4 servers
+        let available = servers.iter().filter(|(server, _)| states.is_available(server)).count();
+        assert_eq!(available, 2);
+    }
+
+    #[test]
+    fn ejected_server_returns_after_the_ejection_time() {
+        let servers = servers(4);
+        let states = BackendStates::new(&servers).with_outlier_detection(Some(OutlierDetectionConfig {
+            base_ejection_time: Duration::from_millis(20),
+            ..config()
+        }));
+        let server = &servers[0].0;
+
+        for _ in 0..3 {
+            states.report_failure(server);
+        }
+        assert!(!states.is_available(server));
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(states.is_available(server));
+        assert!(!states.get(server).unwrap().is_ejected());
+    }
+
+    #[tokio::test]
+    async fn only_the_errors_of_the_server_eject_it() {
+        // the first connection is kept open, the second one is reset after a byte
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut kept, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let _ = kept.read_to_end(&mut Vec::new()).await;
+            });
+            let (mut reset, _) = listener.accept().await.unwrap();
+            let _ = reset.read(&mut [0; 1]).await;
+            reset.set_linger(Some(Duration::ZERO)).unwrap();
+        });
+        let (listen_port, admin_port) = (free_port().await, free_port().await);
+        let config = configure_json("outlier_reset", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Admin": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Outlier_detection": {{ "consecutive_failures": 1, "max_ejection_percent": 100 }},
+            "Servers": [{{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}]
+        }}"#, listen_port, admin_port, backend_port)).unwrap();
+
+        let (stop, stopped) = oneshot::channel::<()>();
+        let (_reload_sender, reloads) = mpsc::channel(1);
+        let mut server = Server::new(config);
+        let server = tokio::spawn(async move {
+            server.run(async { let _ = stopped.await; }, reloads).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // the client resets its connection
+        let client = TcpStream::connect(("127.0.0.1", listen_port)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.set_linger(Some(Duration::ZERO)).unwrap();
+        drop(client);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let (_, body) = admin(admin_port, "GET", "/backends", "").await;
+        assert_eq!((&body[0]["ejected"], &body[0]["total_ejections"]), (&json!(false), &json!(0)));
+
+        // the server resets its connection
+        let mut client = TcpStream::connect(("127.0.0.1", listen_port)).await.unwrap();
+        client.write_all(b"a").await.unwrap();
+        let _ = client.read_to_end(&mut Vec::new()).await;
+        let (_, body) = admin(admin_port, "GET", "/backends", "").await;
+        assert_eq!((&body[0]["ejected"], &body[0]["total_ejections"]), (&json!(true), &json!(1)));
+
+        stop.send(()).unwrap();
+        assert!(server.await.unwrap().is_ok());
+    }
+}
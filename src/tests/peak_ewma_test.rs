@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+    use crate::{
+        balancers::{
+            LoadBalancer,
+            load_balancer_factory,
+            peak_ewma_load_balancer::load_balancer::*
+        },
+        tests::fixtures::*
+    };
+
+    #[test]
+    fn decay_must_be_positive() {
+        let mut balancer = load_balancer_factory::<PeakEwmaLB>(1).unwrap();
+
+        assert_eq!(balancer.set_options(&serde_json::json!({"decay": 0})), Err(INVALID_DECAY));
+        assert_eq!(balancer.set_options(&serde_json::json!({"decay": 500})), Ok(()));
+    }
+
+    #[test]
+    fn slow_server_is_avoided() {
+        let balancer: PeakEwmaLB = create_load_balancer(&[1, 1]);
+        balancer.report_latency(&soc_addr(0), Duration::from_millis(100));
+        balancer.report_latency(&soc_addr(1), Duration::from_millis(10));
+
+        // the fast server is chosen until its cost is higher:
+        // 10ms * (in-flight + 1) > 100ms
+        let fast = soc_addr(1);
+        for _ in 0..9 {
+            assert_eq!(*balancer.next_server(&context()).unwrap(), fast);
+        }
+        assert_eq!(*balancer.next_server(&context()).unwrap(), soc_addr(0));
+    }
+
+    #[test]
+    fn released_requests_lower_the_cost() {
+        let balancer: PeakEwmaLB = create_load_balancer(&[1, 1]);
+        balancer.report_latency(&soc_addr(0), Duration::from_millis(30));
+        balancer.report_latency(&soc_addr(1), Duration::from_millis(10));
+        for _ in 0..3 {
+            let server = balancer.next_server(&context()).unwrap().clone();
+            assert_eq!(server, soc_addr(1));
+            balancer.release(&server);
+        }
+    }
+
+    #[test]
+    fn peak_is_taken_at_once() {
+        let balancer: PeakEwmaLB = create_load_balancer(&[1]);
+        balancer.report_latency(&soc_addr(0), Duration::from_millis(5));
+        balancer.report_latency(&soc_addr(0), Duration::from_millis(50));
+
+        assert_eq!(balancer.latency(&soc_addr(0)), Some(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn lower_latencies_are_averaged_with_decay() {
+        let mut balancer = load_balancer_factory::<PeakEwmaLB>(1).unwrap();
+        balancer.set_decay(Duration::from_millis(10)).unwrap();
+        balancer.insert_socket_address(soc_addr(0), 1).unwrap();
+        balancer.report_latency(&soc_addr(0), Duration::from_millis(100));
+
+        // right after the peak a low latency changes the average very little
+        balancer.report_latency(&soc_addr(0), Duration::from_millis(1));
+        let latency = balancer.latency(&soc_addr(0)).unwrap();
+        assert!(latency > Duration::from_millis(50), "latency: {:?}", latency);
+
+        // after many decay windows the peak is forgotten
+        thread::sleep(Duration::from_millis(100));
+        balancer.report_latency(&soc_addr(0), Duration::from_millis(1));
+        let latency = balancer.latency(&soc_addr(0)).unwrap();
+        assert!(latency < Duration::from_millis(2), "latency: {:?}", latency);
+    }
+
+    #[test]
+    fn weight_lowers_the_cost() {
+        let balancer: PeakEwmaLB = create_load_balancer(&[1, 4]);
+        balancer.report_latency(&soc_addr(0), Duration::from_millis(10));
+        balancer.report_latency(&soc_addr(1), Duration::from_millis(20));
+
+        assert_eq!(balancer.n_of_servers(), 2);
+        assert_eq!(*balancer.next_server(&context()).unwrap(), soc_addr(1));
+    }
+
+    #[test]
+    fn slow_server_is_tried_again_after_the_decay() {
+        let mut balancer = load_balancer_factory::<PeakEwmaLB>(2).unwrap();
+        balancer.set_decay(Duration::from_millis(10)).unwrap();
+        balancer.insert_socket_address(soc_addr(0), 1).unwrap();
+        balancer.insert_socket_address(soc_addr(1), 1).unwrap();
+        balancer.report_latency(&soc_addr(0), Duration::from_millis(100));
+        balancer.report_latency(&soc_addr(1), Duration::from_millis(1));
+        let server = balancer.next_server(&context()).unwrap().clone();
+        assert_eq!(server, soc_addr(1));
+        balancer.release(&server);
+
+        // the spiked server receives no reports, but its average
+        // decays while the other server keeps reporting 1ms
+        thread::sleep(Duration::from_millis(100));
+        balancer.report_latency(&soc_addr(1), Duration::from_millis(1));
+        assert_eq!(*balancer.next_server(&context()).unwrap(), soc_addr(0));
+    }
+
+    #[test]
+    fn latency_is_kept_by_the_next_balancer() {
+        let previous: PeakEwmaLB = create_load_balancer(&[1, 1]);
+        previous.report_latency(&soc_addr(0), Duration::from_millis(100));
+        let mut balancer: PeakEwmaLB = create_load_balancer(&[1, 1]);
+        balancer.inherit(&previous);
+
+        assert_eq!(balancer.latency(&soc_addr(0)), Some(Duration::from_millis(100)));
+        assert_eq!(*balancer.next_server(&context()).unwrap(), soc_addr(1));
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::{
+        balancers::{
+            LoadBalancer,
+            random::random_below,
+            power_of_two_choices_load_balancer::load_balancer::*
+        },
+        tests::fixtures::*
+    };
+
+    #[test]
+    fn random_numbers_are_below_the_bound() {
+        for bound in 1..100 {
+            assert!(random_below(bound) < bound);
+        }
+    }
+
+    #[test]
+    fn in_flight_connections_stay_balanced() {
+        let balancer: PowerOfTwoChoicesLB = create_load_balancer(&[1, 1, 1, 1]);
+        for _ in 0..400 {
+            balancer.next_server(&context()).unwrap();
+        }
+        // with two choices the max load stays very close to the mean
+        for i in 0..4 {
+            let active = balancer.active_connections(&soc_addr(i));
+            assert!(active.abs_diff(100) <= 10, "active connections of server {}: {}", i, active);
+        }
+    }
+
+    #[test]
+    fn two_different_servers_are_always_compared() {
+        // with two servers the less loaded one is always chosen
+        for weights in [[1, 1], [0, 0], [1, 5]] {
+            let balancer: PowerOfTwoChoicesLB = create_load_balancer(&weights);
+            for n in 1..=100 {
+                balancer.next_server(&context()).unwrap();
+                let (first, second) = (balancer.active_connections(&soc_addr(0)), balancer.active_connections(&soc_addr(1)));
+                assert!(first.abs_diff(second) <= 1, "weights {:?}, connection {}: {} and {}", weights, n, first, second);
+            }
+        }
+    }
+
+    #[test]
+    fn sampling_respects_the_weights() {
+        let balancer: PowerOfTwoChoicesLB = create_load_balancer(&[1, 3]);
+        let mut hash = HashMap::new();
+        for _ in 0..4000 {
+            let server = balancer.next_server(&context()).unwrap().clone();
+            // released at once, so only the sampling matters
+            balancer.release(&server);
+            *hash.entry(server).or_insert(0) += 1;
+        }
+        let share = hash[&soc_addr(1)] as f64 / 4000.0;
+
+        assert_eq!(balancer.n_of_servers(), 2);
+        assert!(share > 0.6, "share of the server with weight 3: {}", share);
+    }
+
+    #[test]
+    fn release_decrements_the_in_flight_connections() {
+        let balancer: PowerOfTwoChoicesLB = create_load_balancer(&[1]);
+        let server = balancer.next_server(&context()).unwrap().clone();
+        balancer.next_server(&context()).unwrap();
+        balancer.release(&server);
+
+        assert_eq!(balancer.active_connections(&server), 1);
+    }
+}
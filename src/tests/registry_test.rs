@@ -0,0 +1,68 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::{
+        server::socket_address::*,
+        balancers::{
+            create_balancer,
+            registry::*,
+            Algorithm,
+            RequestContext
+        }
+    };
+
+    fn servers() -> Vec<(SocketAddress, usize)> {
+        weighted_servers(&[1, 1, 1])
+    }
+
+    /// Return a server for each weight, the i-th one on port 9000 + i
+    fn weighted_servers(weights: &[usize]) -> Vec<(SocketAddress, usize)> {
+        weights.iter().enumerate().map(|(i, weight)| {
+            (SocketAddress::new(String::from("127.0.0.1"), (9000 + i).to_string()).unwrap(), *weight)
+        }).collect()
+    }
+
+    #[test]
+    fn every_algorithm_is_registered_once() {
+        let names: HashSet<_> = registrations().iter().map(|registration| registration.name).collect();
+        assert_eq!(names.len(), registrations().len());
+        for registration in registrations() {
+            assert_eq!(Algorithm::from_name(registration.name), Some(registration.algorithm));
+            assert_eq!(registration.algorithm.name(), registration.name);
+        }
+        assert_eq!(Algorithm::from_name("random"), None);
+    }
+
+    #[test]
+    fn every_registered_balancer_works_as_trait_object() {
+        let context = RequestContext::new("127.0.0.1:50000".parse().unwrap());
+        for registration in registrations() {
+            let balancer = create_balancer(registration.algorithm, servers().into_iter().enumerate().collect(), &serde_json::Value::Null).unwrap();
+            let server = balancer.next_server(&context).unwrap();
+            assert!(servers().iter().any(|(socket_address, _)| socket_address == server), "{}", registration.name);
+            balancer.release(server);
+        }
+    }
+
+    #[test]
+    fn servers_with_weight_0_are_chosen_only_when_every_weight_is_0() {
+        // the weights and the ports of the servers that can be chosen
+        let cases: [(&[usize], &[u16]); 4] = [
+            (&[0, 1], &[9001]),
+            (&[0, 2, 1], &[9001, 9002]),
+            (&[1, 0, 0], &[9000]),
+            (&[0, 0, 0], &[9000, 9001, 9002])
+        ];
+        for registration in registrations() {
+            for (weights, expected) in cases {
+                let balancer = create_balancer(registration.algorithm, weighted_servers(weights).into_iter().enumerate().collect(), &serde_json::Value::Null).unwrap();
+                // the connections stay in flight, different clients for the hashes
+                let chosen: HashSet<_> = (0..300u32).map(|n| {
+                    let ip = std::net::Ipv4Addr::from(0xC0A8_0000 + n);
+                    balancer.next_server(&RequestContext::new((ip, 40000).into())).unwrap().port()
+                }).collect();
+                assert_eq!(chosen, expected.iter().copied().collect(), "{} {:?}", registration.name, weights);
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        sync::{mpsc, oneshot},
+        task::JoinHandle
+    };
+    use crate::{
+        balancers::Config,
+        server::app::Server,
+        tests::fixtures::*
+    };
+
+    /// Return the configuration with the given algorithm and servers
+    fn config(name: &str, listen_port: u16, admin_port: u16, algorithm: &str, servers: &[(u16, usize)]) -> Config {
+        let servers: Vec<_> = servers.iter()
+            .map(|(port, weight)| format!(r#"{{ "ipv4": "127.0.0.1", "port": "{}", "weight": {} }}"#, port, weight))
+            .collect();
+        configure_json(name, &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Admin": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Algorithm": "{}",
+            "Servers": [{}]
+        }}"#, listen_port, admin_port, algorithm, servers.join(","))).unwrap()
+    }
+
+    /// Run the server, return the senders for the reloads and the stop
+    fn run(config: Config) -> (mpsc::Sender<Config>, oneshot::Sender<()>, JoinHandle<()>) {
+        let (reload_sender, reloads) = mpsc::channel(1);
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            Server::new(config).run(async { let _ = stopped.await; }, reloads).await.unwrap();
+        });
+        (reload_sender, stop, server)
+    }
+
+    #[tokio::test]
+    async fn valid_reload_replaces_the_servers() {
+        let (first, second) = (backend("first").await, backend("second").await);
+        let (listen_port, admin_port) = (free_port().await, free_port().await);
+        let (reloads, stop, server) = run(config("reload_valid", listen_port, admin_port, "weighted_round_robin", &[(first, 1)]));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(request(listen_port).await, "first");
+
+        reloads.send(config("reload_valid_new", listen_port, admin_port, "least_connections", &[(second, 1)])).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        for _ in 0..3 {
+            assert_eq!(request(listen_port).await, "second");
+        }
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_reload_keeps_the_old_configuration() {
+        let (first, second) = (backend("first").await, backend("second").await);
+        let (listen_port, admin_port) = (free_port().await, free_port().await);
+        let (reloads, stop, server) = run(config("reload_invalid", listen_port, admin_port, "weighted_round_robin", &[(first, 1)]));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // refused by the balancer: too many points on the ring
+        reloads.send(config("reload_invalid_new", listen_port, admin_port, "consistent_hash", &[(second, 10_000)])).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        for _ in 0..3 {
+            assert_eq!(request(listen_port).await, "first");
+        }
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn servers_keep_their_state_across_a_reload() {
+        let (first, second) = (backend("first").await, backend("second").await);
+        let (listen_port, admin_port) = (free_port().await, free_port().await);
+        let servers = [(first, 1), (second, 1)];
+        let (reloads, stop, server) = run(config("reload_state", listen_port, admin_port, "weighted_round_robin", &servers));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut admin = TcpStream::connect(("127.0.0.1", admin_port)).await.unwrap();
+        let drain = format!("POST /backends/127.0.0.1:{}/drain HTTP/1.1\r\nHost: admin\r\nContent-Length: 0\r\n\r\n", first);
+        admin.write_all(drain.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        admin.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "response: {}", response);
+
+        reloads.send(config("reload_state_new", listen_port, admin_port, "smooth_weighted_round_robin", &servers)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        for _ in 0..4 {
+            assert_eq!(request(listen_port).await, "second");
+        }
+        let mut admin = TcpStream::connect(("127.0.0.1", admin_port)).await.unwrap();
+        admin.write_all(b"GET /backends HTTP/1.1\r\nHost: admin\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        admin.read_to_string(&mut response).await.unwrap();
+        let body: serde_json::Value = serde_json::from_str(response.split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(body[0]["drained"], json!(true));
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream}
+    };
+    use crate::{
+        server::socket_address::*,
+        balancers::{
+            Algorithm,
+            RequestContext,
+            create_balancer,
+            registry::registrations
+        },
+        tests::fixtures::*
+    };
+
+    fn servers() -> Vec<(SocketAddress, usize)> {
+        (0..3).map(|i| {
+            (SocketAddress::new(String::from("127.0.0.1"), (9000 + i).to_string()).unwrap(), i + 1)
+        }).collect()
+    }
+
+    /// Excludes the servers one by one, as the retries do, and checks that
+    /// every retry gets a server not tried yet, then None
+    fn retries_never_get_a_tried_server(algorithm: Algorithm) {
+        let servers = servers();
+        let balancer = create_balancer(algorithm, servers.iter().cloned().enumerate().collect(), &serde_json::Value::Null).unwrap();
+
+        for client in 0..20 {
+            let mut context = RequestContext::new(format!("10.0.0.{}:40000", client).parse().unwrap());
+            let mut tried = Vec::new();
+            for _ in 0..servers.len() {
+                let server = balancer.next_server(&context).unwrap().clone();
+                balancer.release(&server);
+                assert!(!tried.contains(&server));
+                tried.push(server.clone());
+                context.exclude(server);
+            }
+            assert!(balancer.next_server(&context).is_none());
+        }
+    }
+
+    #[test]
+    fn every_balancer_skips_the_excluded_servers() {
+        for registration in registrations() {
+            retries_never_get_a_tried_server(registration.algorithm);
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_connect_is_retried_on_another_server() {
+        let (closed, open) = (free_port().await, backend("open").await);
+        let listen_port = free_port().await;
+        let (stop, server) = start("retry_connect", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Retries": {{ "max_retries": 1 }},
+            "Servers": [
+                {{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }},
+                {{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}
+            ]
+        }}"#, listen_port, closed, open));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // the closed port is chosen by half of the connections
+        for _ in 0..4 {
+            assert_eq!(request(listen_port).await, "open");
+        }
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    /// Read the head of a request from the socket, and the body read with it
+    async fn read_head(socket: &mut TcpStream) {
+        let mut head = Vec::new();
+        let mut buf = [0; 1024];
+        while !head.windows(4).any(|end| end == b"\r\n\r\n") {
+            let n = socket.read(&mut buf).await.unwrap();
+            assert!(n > 0);
+            head.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    /// Send a request to the port and return the status of the response
+    async fn http_request(port: u16, request: &str) -> String {
+        let mut socket = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        response[9..12].to_string()
+    }
+
+    #[tokio::test]
+    async fn idempotent_request_is_retried_when_the_server_resets() {
+        // a server that resets every connection after the request head
+        let resetting = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let resetting_port = resetting.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = resetting.accept().await {
+                read_head(&mut socket).await;
+                socket.set_linger(Some(Duration::ZERO)).unwrap();
+            }
+        });
+        let answering = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let answering_port = answering.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = answering.accept().await {
+                read_head(&mut socket).await;
+                let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        let listen_port = free_port().await;
+        let (stop, server) = start("retry_reset", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Mode": "http",
+            "Retries": {{ "max_retries": 1 }},
+            "Servers": [
+                {{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }},
+                {{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}
+            ]
+        }}"#, listen_port, resetting_port, answering_port));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let get = "GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n";
+        for _ in 0..4 {
+            assert_eq!(http_request(listen_port, get).await, "200");
+        }
+        // a request with a body can't be sent again
+        let post = "POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi";
+        let statuses = [http_request(listen_port, post).await, http_request(listen_port, post).await];
+        assert!(statuses.contains(&String::from("502")), "statuses: {:?}", statuses);
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+}
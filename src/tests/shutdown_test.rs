@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+    use tokio::time::{sleep, Instant};
+    use crate::server::shutdown::*;
+
+    #[tokio::test]
+    async fn drain_waits_the_active_connections() {
+        let connections = Arc::new(Connections::new());
+        for i in 0..3 {
+            let guard = connections.track();
+            tokio::spawn(guard.run(sleep(Duration::from_millis(20 * i))));
+        }
+        assert_eq!(connections.active(), 3);
+
+        let report = connections.drain(Duration::from_secs(5)).await;
+        assert_eq!(report, DrainReport { drained: 3, aborted: 0 });
+        assert_eq!(connections.active(), 0);
+    }
+
+    #[tokio::test]
+    async fn drain_aborts_the_connections_after_the_timeout() {
+        let connections = Arc::new(Connections::new());
+        let quick = connections.track();
+        tokio::spawn(quick.run(sleep(Duration::from_millis(10))));
+        let hung = connections.track();
+        let hung = tokio::spawn(hung.run(std::future::pending::<()>()));
+
+        let start = Instant::now();
+        let report = connections.drain(Duration::from_millis(100)).await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(report, DrainReport { drained: 1, aborted: 1 });
+        // the aborted task ends instead of waiting forever
+        hung.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn drain_without_connections_ends_immediately() {
+        let connections = Connections::new();
+        let report = connections.drain(Duration::from_secs(5)).await;
+        assert_eq!(report, DrainReport { drained: 0, aborted: 0 });
+    }
+
+    #[tokio::test]
+    async fn connections_know_when_the_shutdown_starts() {
+        let connections = Arc::new(Connections::new());
+        let guard = connections.track();
+        let waiting = Arc::clone(&connections);
+        let task = tokio::spawn(guard.run(async move {
+            waiting.draining().await;
+            assert!(waiting.is_draining());
+        }));
+
+        sleep(Duration::from_millis(20)).await;
+        assert!(!connections.is_draining());
+        let report = connections.drain(Duration::from_secs(5)).await;
+        assert_eq!(report, DrainReport { drained: 1, aborted: 0 });
+        task.await.unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, thread};
+    use crate::{
+        balancers::{
+            LoadBalancer,
+            smooth_weighted_load_balancer::load_balancer::*
+        },
+        tests::fixtures::*
+    };
+
+    #[test]
+    fn weights_5_1_1_are_interleaved() {
+        let balancer: SmoothWeightedRoundRobinLB = create_load_balancer(&[5, 1, 1]);
+        let sequence: Vec<u16> = (0..7)
+            .map(|_| balancer.next_server(&context()).unwrap().port())
+            .collect();
+
+        assert_eq!(balancer.n_of_servers(), 3);
+        assert_eq!(sequence, [9000, 9000, 9001, 9000, 9002, 9000, 9000]);
+    }
+
+    #[test]
+    fn concurrent_requests_keep_the_proportions() {
+        let balancer = Arc::new(create_load_balancer::<SmoothWeightedRoundRobinLB>(&[1, 2, 3, 1, 2]));
+        let mut handles = vec![];
+        for _ in 0..3 {
+            let balancer = Arc::clone(&balancer);
+            handles.push(thread::spawn(move || {
+                let mut hash = HashMap::new();
+                for _ in 0..(9 * 10) {
+                    *hash.entry(balancer.next_server(&context()).unwrap().get()).or_insert(0) += 1;
+                }
+                hash
+            }));
+        }
+        let mut total: HashMap<String, usize> = HashMap::new();
+        for handle in handles {
+            for (key, n) in handle.join().unwrap() {
+                *total.entry(key).or_insert(0) += n;
+            }
+        }
+
+        assert_eq!(30, total["127.0.0.1:9000"]);
+        assert_eq!(60, total["127.0.0.1:9001"]);
+        assert_eq!(90, total["127.0.0.1:9002"]);
+        assert_eq!(30, total["127.0.0.1:9003"]);
+        assert_eq!(60, total["127.0.0.1:9004"]);
+    }
+
+    #[test]
+    fn sequence_continues_in_the_next_balancer() {
+        let previous: SmoothWeightedRoundRobinLB = create_load_balancer(&[5, 1, 1]);
+        for _ in 0..3 {
+            previous.next_server(&context()).unwrap();
+        }
+        let mut balancer: SmoothWeightedRoundRobinLB = create_load_balancer(&[5, 1, 1]);
+        balancer.inherit(&previous);
+        let sequence: Vec<u16> = (0..4)
+            .map(|_| balancer.next_server(&context()).unwrap().port())
+            .collect();
+
+        assert_eq!(sequence, [9000, 9002, 9000, 9000]);
+    }
+}
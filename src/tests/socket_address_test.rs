@@ -161,4 +161,85 @@ mod tests {
 
         assert!(p.is_err());
     }
+
+    #[test]
+    fn should_pass_ipv6_in_canonical_form() {
+        let p = SocketAddress::new(
+            String::from("2001:DB8:0:0::1"),
+            String::from("8080")
+        ).unwrap();
+
+        assert!(p.is_ipv6());
+        assert_eq!(p.get(), "[2001:db8::1]:8080");
+        assert_eq!(p, SocketAddress::new(String::from("2001:db8::1"), String::from("8080")).unwrap());
+    }
+
+    #[test]
+    fn should_not_pass_invalid_ipv6() {
+        assert_eq!(SocketAddress::new(String::from("2001:db8::g"), String::from("80")), Err(IPV6_ERROR));
+        assert_eq!(SocketAddress::new(String::from("[::1]"), String::from("80")), Err(IPV6_ERROR));
+        assert_eq!(SocketAddress::new(String::from("::1"), String::from("port")), Err(PORT_NUMBER_ERROR));
+    }
+
+    #[test]
+    fn should_pass_zone_id_only_on_link_local() {
+        let p = SocketAddress::new(String::from("fe80::1%3"), String::from("80")).unwrap();
+        assert_eq!(p.get(), "[fe80::1%3]:80");
+        assert_eq!(p.get_ipv4(), "fe80::1%3");
+        match p.to_socket_addr() {
+            std::net::SocketAddr::V6(address) => assert_eq!(address.scope_id(), 3),
+            address => panic!("{address} isn't IPv6")
+        }
+
+        assert_eq!(SocketAddress::new(String::from("2001:db8::1%eth0"), String::from("80")), Err(ZONE_ID_ERROR));
+        assert_eq!(SocketAddress::new(String::from("fe80::1%"), String::from("80")), Err(IPV6_ERROR));
+    }
+
+    #[test]
+    fn should_not_pass_unknown_zone_id() {
+        let p = SocketAddress::new(String::from("fe80::1%no_such_interface"), String::from("80"));
+        assert_eq!(p, Err(UNKNOWN_ZONE_ID));
+    }
+
+    #[test]
+    fn ipv4_to_socket_addr() {
+        let p = SocketAddress::new(String::from("127.0.0.1"), String::from("8080")).unwrap();
+        assert_eq!(p.to_socket_addr(), "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(p.get_ipv4(), "127.0.0.1");
+        assert_eq!(p.get_port_number(), "8080");
+        assert_eq!((p.ip(), p.port()), ("127.0.0.1".parse().unwrap(), 8080));
+        assert_eq!(SocketAddress::from(p.to_socket_addr()), p);
+    }
+
+    #[test]
+    fn should_not_pass_port_0_or_leading_zero_octets() {
+        assert_eq!(SocketAddress::new(String::from("127.0.0.1"), String::from("0")), Err(PORT_NUMBER_ERROR));
+        assert_eq!(SocketAddress::new(String::from("127.0.0.1"), String::from("+80")), Err(PORT_NUMBER_ERROR));
+        assert_eq!(SocketAddress::new(String::from("127.0.0.01"), String::from("80")), Err(IPV4_ERROR));
+        assert_eq!(SocketAddress::new(String::from("010.0.0.1"), String::from("80")), Err(IPV4_ERROR));
+    }
+
+    #[test]
+    fn should_parse_from_str() {
+        let p: SocketAddress = "127.0.0.1:8080".parse().unwrap();
+        assert_eq!(p, SocketAddress::new(String::from("127.0.0.1"), String::from("8080")).unwrap());
+        assert_eq!(SocketAddress::try_from("[::1]:80").unwrap().get(), "[::1]:80");
+        assert_eq!("[fe80::1%3]:80".parse::<SocketAddress>().unwrap().to_string(), "[fe80::1%3]:80");
+
+        assert_eq!("127.0.0.1".parse::<SocketAddress>(), Err(SocketAddressError::MissingPort));
+        assert_eq!("::1:80".parse::<SocketAddress>(), Err(SocketAddressError::MissingBrackets));
+        assert_eq!("[::1:80".parse::<SocketAddress>(), Err(SocketAddressError::InvalidIpv6));
+        assert_eq!("1.1.1.1:99999".parse::<SocketAddress>(), Err(SocketAddressError::InvalidPort));
+        assert_eq!(SocketAddressError::InvalidPort.to_string(), PORT_NUMBER_ERROR);
+    }
+
+    #[test]
+    fn should_serialize_as_a_string() {
+        let p: SocketAddress = "[2001:db8::1]:443".parse().unwrap();
+        assert_eq!(serde_json::to_string(&p).unwrap(), r#""[2001:db8::1]:443""#);
+        assert_eq!(serde_json::from_str::<SocketAddress>(r#""[2001:db8::1]:443""#).unwrap(), p);
+
+        let error = serde_json::from_str::<SocketAddress>(r#""1.1.1.1:0""#).unwrap_err();
+        assert!(error.to_string().contains(PORT_NUMBER_ERROR));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,244 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::{
+        io::{duplex, AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream}
+    };
+    use crate::{server::timeouts::*, tests::fixtures::*};
+
+    fn config() -> TimeoutConfig {
+        TimeoutConfig {
+            client_first_byte: Some(Duration::from_millis(50)),
+            client_idle: Some(Duration::from_millis(100)),
+            backend_idle: Some(Duration::from_millis(50)),
+            ..TimeoutConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn with_timeout_returns_the_kind_of_timeout() {
+        let before = TimeoutKind::Connect.count();
+        let pending = std::future::pending::<std::io::Result<()>>();
+        let error = with_timeout(Some(Duration::from_millis(10)), TimeoutKind::Connect, pending)
+            .await.unwrap_err();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+        assert_eq!(TimeoutKind::of(&error), Some(TimeoutKind::Connect));
+        assert!(TimeoutKind::Connect.count() > before);
+    }
+
+    #[tokio::test]
+    async fn with_timeout_disabled_waits_the_future() {
+        let result = with_timeout(None, TimeoutKind::Lifetime, async { Ok(42) }).await;
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn first_byte_timeout_then_idle_timeout() {
+        let (client, mut peer) = duplex(1024);
+        let mut client = TimedStream::new(client, Side::Client, &config());
+        let mut buf = [0; 16];
+
+        let error = client.read(&mut buf).await.unwrap_err();
+        assert_eq!(TimeoutKind::of(&error), Some(TimeoutKind::ClientFirstByte));
+
+        peer.write_all(b"hello").await.unwrap();
+        assert_eq!(client.read(&mut buf).await.unwrap(), 5);
+
+        let error = client.read(&mut buf).await.unwrap_err();
+        assert_eq!(TimeoutKind::of(&error), Some(TimeoutKind::ClientIdle));
+    }
+
+    #[tokio::test]
+    async fn idle_timeout_is_reset_by_the_reads() {
+        let (client, mut peer) = duplex(1024);
+        let mut client = TimedStream::new(client, Side::Client, &config());
+        let mut buf = [0; 16];
+
+        // a byte every 60 ms, less than the idle timeout (100 ms)
+        // but more than the first byte one (50 ms)
+        peer.write_all(b"a").await.unwrap();
+        for _ in 0..4 {
+            assert_eq!(client.read(&mut buf).await.unwrap(), 1);
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            peer.write_all(b"a").await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn blocked_write_gives_the_idle_timeout() {
+        // the peer never reads, the buffer fills up
+        let (backend, _peer) = duplex(8);
+        let mut backend = TimedStream::new(backend, Side::Backend, &config());
+
+        let error = backend.write_all(&[0; 64]).await.unwrap_err();
+        assert_eq!(TimeoutKind::of(&error), Some(TimeoutKind::BackendIdle));
+    }
+
+    #[tokio::test]
+    async fn no_timeouts_configured_never_fail() {
+        let (client, mut peer) = duplex(1024);
+        let mut client = TimedStream::new(client, Side::Client, &TimeoutConfig::default());
+        let mut buf = [0; 16];
+
+        let read = tokio::spawn(async move { client.read(&mut buf).await.unwrap() });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        peer.write_all(b"late").await.unwrap();
+        assert_eq!(read.await.unwrap(), 4);
+    }
+
+    #[test]
+    fn only_server_timeouts_are_backend_failures() {
+        assert!(TimeoutKind::Connect.is_backend());
+        assert!(TimeoutKind::BackendFirstByte.is_backend());
+        assert!(TimeoutKind::BackendIdle.is_backend());
+        assert!(!TimeoutKind::ClientFirstByte.is_backend());
+        assert!(!TimeoutKind::ClientIdle.is_backend());
+        assert!(!TimeoutKind::Lifetime.is_backend());
+    }
+
+    #[tokio::test]
+    async fn errors_are_tagged_with_the_side() {
+        let (backend, peer) = duplex(8);
+        let mut backend = TimedStream::new(backend, Side::Backend, &config());
+        drop(peer);
+
+        let error = backend.write_all(b"hello").await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe);
+        assert_eq!(Side::of(&error), Some(Side::Backend));
+        assert_eq!(Side::of(&TimeoutKind::ClientIdle.error()), Some(Side::Client));
+        assert_eq!(Side::of(&std::io::Error::from(std::io::ErrorKind::BrokenPipe)), None);
+    }
+
+    #[tokio::test]
+    async fn writes_reset_the_idle_time_of_the_read_half() {
+        let (reader, mut reader_peer) = duplex(1024);
+        let (writer, mut writer_peer) = duplex(1024);
+        let config = TimeoutConfig { client_idle: Some(Duration::from_millis(100)), ..TimeoutConfig::default() };
+        let (mut reader, mut writer) = TimedStream::halves(reader, writer, Side::Client, &config);
+        let mut buf = [0; 16];
+
+        // a write every 40 ms for 240 ms, the idle timeout is 100 ms
+        let read = tokio::spawn(async move { reader.read(&mut buf).await.unwrap() });
+        for _ in 0..6 {
+            writer.write_all(b"a").await.unwrap();
+            assert_eq!(writer_peer.read(&mut buf).await.unwrap(), 1);
+            tokio::time::sleep(Duration::from_millis(40)).await;
+        }
+        reader_peer.write_all(b"done").await.unwrap();
+        assert_eq!(read.await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn deferred_first_byte_starts_after_the_request() {
+        let config = TimeoutConfig { backend_first_byte: Some(Duration::from_millis(50)), ..TimeoutConfig::default() };
+        let (reader, _reader_peer) = duplex(1024);
+        let (writer, _writer_peer) = duplex(1024);
+        let (mut reader, writer) = TimedStream::halves(reader, writer, Side::Backend, &config);
+        reader.defer_first_byte();
+
+        let started = tokio::time::Instant::now();
+        let read = tokio::spawn(async move { reader.read(&mut [0; 16]).await.unwrap_err() });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!read.is_finished());
+        writer.start_first_byte();
+        let error = read.await.unwrap();
+        assert_eq!(TimeoutKind::of(&error), Some(TimeoutKind::BackendFirstByte));
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn stream_in_one_direction_is_not_idle() {
+        // the server streams for 300 ms, the client only reads
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            for _ in 0..10 {
+                socket.write_all(b"a").await.unwrap();
+                tokio::time::sleep(Duration::from_millis(30)).await;
+            }
+        });
+        let listen_port = free_port().await;
+        let (stop, server) = start("timeouts_stream", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Timeouts": {{ "client_idle": 100, "backend_idle": 100 }},
+            "Servers": [{{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}]
+        }}"#, listen_port, backend_port));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(request(listen_port).await, "a".repeat(10));
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn slow_request_body_is_not_a_server_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"0123456789") {
+                let mut buf = [0; 1024];
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        let listen_port = free_port().await;
+        let (stop, server) = start("timeouts_body", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Mode": "http",
+            "Timeouts": {{ "backend_first_byte": 100 }},
+            "Servers": [{{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}]
+        }}"#, listen_port, backend_port));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // the body takes 300 ms, more than the first byte timeout
+        let mut client = TcpStream::connect(("127.0.0.1", listen_port)).await.unwrap();
+        client.write_all(b"POST / HTTP/1.1\r\nHost: test\r\nContent-Length: 10\r\n\r\n").await.unwrap();
+        for byte in b"0123456789" {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            client.write_all(&[*byte]).await.unwrap();
+        }
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "response: {}", response);
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_counted_in_the_stats() {
+        // the server never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let _ = socket.read_to_end(&mut Vec::new()).await;
+        });
+        let (listen_port, admin_port) = (free_port().await, free_port().await);
+        let (stop, server) = start("timeouts_stats", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Admin": {{ "ipv4": "127.0.0.1", "port": "{}" }},
+            "Timeouts": {{ "backend_idle": 50 }},
+            "Servers": [{{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}]
+        }}"#, listen_port, admin_port, backend_port));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let before = TimeoutKind::BackendIdle.count();
+        assert_eq!(request(listen_port).await, "");
+        let (status, body) = admin(admin_port, "GET", "/stats", "").await;
+        assert_eq!((status, &body["total_ejections"]), (200, &serde_json::json!(0)));
+        assert!(body["timeouts"][TimeoutKind::BackendIdle.key()].as_u64().unwrap() > before as u64);
+        assert_eq!(admin(admin_port, "POST", "/stats", "").await.0, 405);
+
+        stop.send(()).unwrap();
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use crate::balancers::{
+        configure,
+        validation::*,
+        Config
+    };
+
+    fn configure_json(name: &str, json: &str) -> Config {
+        let path = std::env::temp_dir()
+            .join(format!("load_balancer_validation_{}_{}.json", name, std::process::id()));
+        std::fs::write(&path, json).unwrap();
+        let config = configure(&path);
+        std::fs::remove_file(&path).unwrap();
+        config.unwrap()
+    }
+
+    fn paths(warnings: &[ConfigWarning]) -> Vec<&str> {
+        warnings.iter().map(|warning| warning.path.as_str()).collect()
+    }
+
+    #[test]
+    fn clean_config_has_no_warnings() {
+        let config = configure_json("clean", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Servers": [
+                { "ipv4": "127.0.0.1", "port": "9000", "weight": 1 },
+                { "ipv4": "127.0.0.1", "port": "9001", "weight": 2 }
+            ]
+        }"#);
+        assert!(config_warnings(&config).is_empty());
+    }
+
+    #[test]
+    fn duplicates_zero_weights_and_collisions_are_warned() {
+        let config = configure_json("warnings", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "9000" },
+            "Servers": [
+                { "ipv4": "127.0.0.1", "port": "9000", "weight": 1 },
+                { "ipv4": "127.0.0.1", "port": "9001", "weight": 0 },
+                { "ipv4": "127.0.0.1", "port": "9001", "weight": 1 }
+            ]
+        }"#);
+        assert_eq!(
+            paths(&config_warnings(&config)),
+            vec!["Listen_to", "Servers[1].weight", "Servers[2]"]
+        );
+    }
+
+    #[test]
+    fn unspecified_listener_collides_with_loopback_servers() {
+        let config = configure_json("unspecified", r#"{
+            "Listen_to": { "ipv4": "0.0.0.0", "port": "9000" },
+            "Servers": [
+                { "ipv4": "127.0.0.1", "port": "9000", "weight": 1 },
+                { "ipv4": "10.0.0.1", "port": "9000", "weight": 1 }
+            ]
+        }"#);
+        assert_eq!(paths(&config_warnings(&config)), vec!["Listen_to"]);
+    }
+
+    #[test]
+    fn unspecified_ipv6_listener_collides_with_ipv4_only_if_dual_stack() {
+        let json = |dual_stack: bool| format!(r#"{{
+            "Listen_to": {{ "ipv6": "::", "port": "9000", "dual_stack": {} }},
+            "Servers": [
+                {{ "ipv6": "::1", "port": "9000", "weight": 1 }},
+                {{ "ipv4": "127.0.0.1", "port": "9000", "weight": 1 }}
+            ]
+        }}"#, dual_stack);
+        let config = configure_json("dual_stack", &json(true));
+        assert_eq!(paths(&config_warnings(&config)), vec!["Listen_to", "Listen_to"]);
+        let config = configure_json("ipv6_only", &json(false));
+        assert_eq!(paths(&config_warnings(&config)), vec!["Listen_to"]);
+    }
+
+    #[test]
+    fn paths_skip_the_servers_with_a_hostname() {
+        let config = configure_json("hosts", r#"{
+            "Listen_to": { "ipv4": "127.0.0.1", "port": "8080" },
+            "Servers": [
+                { "host": "api.internal", "port": "9000", "weight": 0 },
+                { "ipv4": "127.0.0.1", "port": "9001", "weight": 1 },
+                { "ipv4": "127.0.0.1", "port": "9001", "weight": 1 }
+            ]
+        }"#);
+        let warnings = config_warnings(&config);
+        assert_eq!(paths(&warnings), vec!["Servers[2]", "Servers[0].weight"]);
+        assert!(warnings[0].message.ends_with("duplicate of Servers[1]"));
+    }
+
+    #[tokio::test]
+    async fn probe_warns_about_unreachable_servers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let reachable = listener.local_addr().unwrap().port();
+        // bound and dropped, so nothing listens on it
+        let unreachable = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+
+        let config = configure_json("probe", &format!(r#"{{
+            "Listen_to": {{ "ipv4": "127.0.0.1", "port": "8080" }},
+            "Servers": [
+                {{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }},
+                {{ "ipv4": "127.0.0.1", "port": "{}", "weight": 1 }}
+            ]
+        }}"#, reachable, unreachable));
+        assert_eq!(paths(&probe_servers(&config, &[]).await), vec!["Servers[1]"]);
+    }
+
+    #[test]
+    fn admin_out_of_loopback_is_warned() {
+        let config = configure_json("admin", r#"{
+            "Listen_to": { "ipv4": "0.0.0.0", "port": "8080" },
+            "Admin": { "ipv4": "0.0.0.0", "port": "8081" },
+            "Servers": [{ "ipv4": "10.0.0.1", "port": "9000", "weight": 1 }]
+        }"#);
+        assert_eq!(paths(&config_warnings(&config)), vec!["Admin"]);
+    }
+}